@@ -166,6 +166,30 @@ fn bench_interpreter(c: &mut Criterion) {
         })
     });
 
+    // Deleting and re-adding elements, then traversing what is left
+    group.bench_function("array_delete", |b| {
+        b.iter(|| {
+            run_awk(
+                black_box("BEGIN { for (i = 1; i <= 1000; i++) arr[i] = i; for (i = 1; i <= 1000; i += 2) delete arr[i]; for (i = 1; i <= 100; i++) arr[\"k\" i] = i; for (k in arr) n++; print n }"),
+                ""
+            )
+        })
+    });
+
+    // String keys, as when counting words
+    let words = (0..1000)
+        .map(|i| format!("word{} other{}", i % 50, i % 7))
+        .collect::<Vec<_>>()
+        .join("\n");
+    group.bench_function("array_word_count", |b| {
+        b.iter(|| {
+            run_awk(
+                black_box("{ for (i = 1; i <= NF; i++) count[$i]++ } END { for (w in count) n += count[w]; print n }"),
+                black_box(&words),
+            )
+        })
+    });
+
     // Printf formatting
     group.bench_function("printf_formatting", |b| {
         b.iter(|| {
@@ -202,6 +226,22 @@ fn bench_e2e_throughput(c: &mut Criterion) {
         });
     }
 
+    // A single long record under a regex RS that only matches at its end
+    let input = format!("{}XXXX", "a".repeat(4 << 20));
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_with_input(
+        BenchmarkId::new("regex_rs_long_record", "4MB"),
+        &input,
+        |b, input| {
+            b.iter(|| {
+                run_awk(
+                    black_box(r#"BEGIN { RS = "X+" } { n += length($0) } END { print n }"#),
+                    black_box(input),
+                )
+            })
+        },
+    );
+
     group.finish();
 }
 
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::error::SourceLocation;
 
 /// A complete AWK program
@@ -144,10 +146,19 @@ pub enum Stmt {
     ForIn {
         var: String,
         array: String,
+        /// Subscripts selecting a subarray: for (k in a\[i\])
+        path: Vec<Vec<Expr>>,
         body: Box<Stmt>,
         location: SourceLocation,
     },
 
+    /// Switch statement (gawk extension)
+    Switch {
+        subject: Expr,
+        cases: Vec<SwitchCase>,
+        location: SourceLocation,
+    },
+
     /// Block of statements
     Block(Block),
 
@@ -178,6 +189,8 @@ pub enum Stmt {
     /// Delete statement: delete array\[index\]
     Delete {
         array: String,
+        /// Subscripts of the enclosing subarray: delete a\[i\]\[j\]
+        path: Vec<Vec<Expr>>,
         index: Vec<Expr>,
         location: SourceLocation,
     },
@@ -193,6 +206,40 @@ pub enum Stmt {
     Empty,
 }
 
+/// One `case` or `default` of a switch statement
+///
+/// Control falls through into the following case unless the body breaks.
+#[derive(Debug, Clone)]
+pub struct SwitchCase {
+    pub label: CaseLabel,
+    pub body: Vec<Stmt>,
+    pub location: SourceLocation,
+}
+
+/// The constant a switch case is compared with
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseLabel {
+    /// case 1:
+    Number(f64),
+    /// case "text":
+    String(String),
+    /// case /regex/:
+    Regex(String),
+    /// default:
+    Default,
+}
+
+impl fmt::Display for CaseLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaseLabel::Number(n) => write!(f, "{}", n),
+            CaseLabel::String(s) => write!(f, "\"{}\"", s),
+            CaseLabel::Regex(r) => write!(f, "/{}/", r),
+            CaseLabel::Default => f.write_str("default"),
+        }
+    }
+}
+
 /// Output redirection for print/printf
 #[derive(Debug, Clone)]
 pub enum OutputRedirect {
@@ -202,6 +249,8 @@ pub enum OutputRedirect {
     Append(Expr),
     /// | command
     Pipe(Expr),
+    /// |& command (gawk coprocess)
+    Coprocess(Expr),
 }
 
 /// Input source for getline
@@ -211,6 +260,8 @@ pub enum GetlineInput {
     File(Box<Expr>),
     /// command |
     Pipe(Box<Expr>),
+    /// command |& (gawk coprocess)
+    Coprocess(Box<Expr>),
 }
 
 /// Expression types
@@ -225,6 +276,9 @@ pub enum Expr {
     /// Regex literal
     Regex(String, SourceLocation),
 
+    /// Strongly typed regexp constant: @/regex/ (gawk extension)
+    TypedRegex(String, SourceLocation),
+
     /// Variable reference
     Var(String, SourceLocation),
 
@@ -234,6 +288,8 @@ pub enum Expr {
     /// Array access: arr\[index\] or arr\[i,j\]
     ArrayAccess {
         array: String,
+        /// Subscripts of the enclosing subarray: arr\[i\]\[j\] (gawk arrays of arrays)
+        path: Vec<Vec<Expr>>,
         indices: Vec<Expr>,
         location: SourceLocation,
     },
@@ -288,10 +344,20 @@ pub enum Expr {
         location: SourceLocation,
     },
 
+    /// Indirect function call: @var(args) calls the function named by var
+    /// (gawk extension)
+    IndirectCall {
+        var: String,
+        args: Vec<Expr>,
+        location: SourceLocation,
+    },
+
     /// Array membership: (key) in array
     InArray {
         key: Vec<Expr>,
         array: String,
+        /// Subscripts selecting a subarray: (key) in arr\[i\]
+        path: Vec<Vec<Expr>>,
         location: SourceLocation,
     },
 
@@ -323,6 +389,7 @@ impl Expr {
             Expr::Number(_, loc)
             | Expr::String(_, loc)
             | Expr::Regex(_, loc)
+            | Expr::TypedRegex(_, loc)
             | Expr::Var(_, loc)
             | Expr::Field(_, loc)
             | Expr::ArrayAccess { location: loc, .. }
@@ -335,11 +402,12 @@ impl Expr {
             | Expr::PostDecrement(_, loc)
             | Expr::Ternary { location: loc, .. }
             | Expr::Call { location: loc, .. }
+            | Expr::IndirectCall { location: loc, .. }
             | Expr::InArray { location: loc, .. }
             | Expr::Match { location: loc, .. }
             | Expr::Concat(_, loc)
             | Expr::Getline { location: loc, .. }
-            | Expr::Group(_, loc) => *loc,
+            | Expr::Group(_, loc) => loc.clone(),
         }
     }
 }
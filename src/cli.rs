@@ -0,0 +1,347 @@
+//! Command line parsing for the awk-rs binary
+//!
+//! Follows getopt conventions: short options may be grouped (`-Pv x=1`) and
+//! take their argument attached or as the next word (`-vx=1`, `-v x=1`); long
+//! options take `--name=value` or `--name value` and may be abbreviated to any
+//! unique prefix. Option processing stops at `--` or at the first operand.
+
+use std::fmt;
+
+use crate::dialect::Dialect;
+use crate::interpreter::split_assignment;
+use crate::lexer::unescape;
+
+/// Where a piece of the program comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramSource {
+    /// `-f file`, where "-" means standard input
+    File(String),
+    /// `-e text`, or the program given as the first operand
+    Text(String),
+}
+
+/// Everything the command line asks for
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    /// `-F`, after escape processing
+    pub field_separator: Option<String>,
+    /// `-v` assignments, in order (values are unescaped by the interpreter)
+    pub assignments: Vec<(String, String)>,
+    /// Program files and text, in order
+    pub sources: Vec<ProgramSource>,
+    /// `-i` library files
+    pub includes: Vec<String>,
+    pub dialect: Dialect,
+    /// `--non-decimal-data`: hex and octal input data are numbers
+    pub non_decimal_data: bool,
+    /// Input files and `var=value` operands
+    pub operands: Vec<String>,
+    pub help: bool,
+    pub version: bool,
+}
+
+/// A command line that cannot be used; the program exits with status 2
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Short options that take an argument
+const SHORT_WITH_ARG: &str = "Fvfei";
+/// Short options without an argument
+const SHORT_FLAGS: &str = "Pcnh";
+
+/// Long options and whether they take an argument
+const LONG_OPTIONS: &[(&str, bool)] = &[
+    ("field-separator", true),
+    ("assign", true),
+    ("file", true),
+    ("source", true),
+    ("include", true),
+    ("posix", false),
+    ("traditional", false),
+    ("compat", false),
+    ("non-decimal-data", false),
+    ("help", false),
+    ("version", false),
+];
+
+/// Parse the arguments after the program name
+pub fn parse_args(args: &[String]) -> Result<Options, UsageError> {
+    let mut options = Options::default();
+    let mut i = 0;
+
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+
+        if arg == "--" {
+            break;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let (name, takes_arg) = find_long_option(name)?;
+            let value = if takes_arg {
+                match attached {
+                    Some(value) => Some(value),
+                    None if i < args.len() => {
+                        i += 1;
+                        Some(args[i - 1].clone())
+                    }
+                    None => {
+                        return Err(UsageError(format!(
+                            "option '--{}' requires an argument",
+                            name
+                        )));
+                    }
+                }
+            } else if attached.is_some() {
+                return Err(UsageError(format!(
+                    "option '--{}' doesn't allow an argument",
+                    name
+                )));
+            } else {
+                None
+            };
+            apply_long(&mut options, name, value)?;
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            let group: Vec<char> = arg[1..].chars().collect();
+            let mut j = 0;
+            while j < group.len() {
+                let c = group[j];
+                j += 1;
+                if SHORT_FLAGS.contains(c) {
+                    apply_short(&mut options, c, None)?;
+                } else if SHORT_WITH_ARG.contains(c) {
+                    // The rest of the group, or the next word, is the argument
+                    let value = if j < group.len() {
+                        group[j..].iter().collect()
+                    } else if i < args.len() {
+                        i += 1;
+                        args[i - 1].clone()
+                    } else {
+                        return Err(UsageError(format!(
+                            "option requires an argument -- '{}'",
+                            c
+                        )));
+                    };
+                    apply_short(&mut options, c, Some(value))?;
+                    break;
+                } else {
+                    return Err(UsageError(format!("invalid option -- '{}'", c)));
+                }
+            }
+        } else {
+            // The first operand ends the options
+            i -= 1;
+            break;
+        }
+    }
+
+    let mut operands = args[i..].iter();
+    if options.sources.is_empty() && !options.help && !options.version {
+        match operands.next() {
+            Some(program) => options.sources.push(ProgramSource::Text(program.clone())),
+            None => return Err(UsageError("no program text given".to_string())),
+        }
+    }
+    options.operands = operands.cloned().collect();
+
+    // -F is unescaped once the dialect is known, whatever the option order
+    if let Some(fs) = &options.field_separator {
+        options.field_separator = Some(field_separator(fs, options.dialect)?);
+    }
+    Ok(options)
+}
+
+/// Resolve a possibly abbreviated long option name
+fn find_long_option(name: &str) -> Result<(&'static str, bool), UsageError> {
+    if let Some(&(full, takes_arg)) = LONG_OPTIONS.iter().find(|(full, _)| *full == name) {
+        return Ok((full, takes_arg));
+    }
+    let matches: Vec<_> = LONG_OPTIONS
+        .iter()
+        .filter(|(full, _)| !name.is_empty() && full.starts_with(name))
+        .collect();
+    match matches.as_slice() {
+        [(full, takes_arg)] => Ok((full, *takes_arg)),
+        [] => Err(UsageError(format!("unrecognized option '--{}'", name))),
+        _ => Err(UsageError(format!("option '--{}' is ambiguous", name))),
+    }
+}
+
+fn apply_long(options: &mut Options, name: &str, value: Option<String>) -> Result<(), UsageError> {
+    let short = match name {
+        "field-separator" => 'F',
+        "assign" => 'v',
+        "file" => 'f',
+        "source" => 'e',
+        "include" => 'i',
+        "posix" => 'P',
+        "traditional" | "compat" => 'c',
+        "non-decimal-data" => 'n',
+        "help" => 'h',
+        "version" => {
+            options.version = true;
+            return Ok(());
+        }
+        _ => unreachable!("unknown long option {}", name),
+    };
+    apply_short(options, short, value)
+}
+
+fn apply_short(
+    options: &mut Options,
+    option: char,
+    value: Option<String>,
+) -> Result<(), UsageError> {
+    let value = value.unwrap_or_default();
+    match option {
+        'F' => options.field_separator = Some(value),
+        'v' => match split_assignment(&value) {
+            Some((name, value)) => options
+                .assignments
+                .push((name.to_string(), value.to_string())),
+            None => {
+                return Err(UsageError(format!(
+                    "invalid variable assignment '{}', expected var=value",
+                    value
+                )));
+            }
+        },
+        'f' => options.sources.push(ProgramSource::File(value)),
+        'e' => options.sources.push(ProgramSource::Text(value)),
+        'i' => options.includes.push(value),
+        'P' => options.dialect = Dialect::Posix,
+        'c' => options.dialect = Dialect::Traditional,
+        'n' => options.non_decimal_data = true,
+        'h' => options.help = true,
+        _ => unreachable!("unknown short option {}", option),
+    }
+    Ok(())
+}
+
+/// The field separator for `-F value`: escapes are processed, and a lone `t`
+/// means a tab, as POSIX requires
+fn field_separator(value: &str, dialect: Dialect) -> Result<String, UsageError> {
+    if value == "t" {
+        Ok("\t".to_string())
+    } else {
+        unescape(value, dialect).map_err(|e| UsageError(format!("-F: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, UsageError> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_program_and_operands() {
+        let options = parse(&["{ print }", "a.txt", "x=1"]).unwrap();
+        assert_eq!(
+            options.sources,
+            vec![ProgramSource::Text("{ print }".to_string())]
+        );
+        assert_eq!(options.operands, vec!["a.txt", "x=1"]);
+    }
+
+    #[test]
+    fn test_grouped_and_attached_short_options() {
+        let options = parse(&["-PvX=1", "-F:", "-v", "y=2", "prog"]).unwrap();
+        assert_eq!(options.dialect, Dialect::Posix);
+        assert_eq!(
+            options.assignments,
+            vec![
+                ("X".to_string(), "1".to_string()),
+                ("y".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(options.field_separator.as_deref(), Some(":"));
+    }
+
+    #[test]
+    fn test_long_options() {
+        let options = parse(&[
+            "--field-separator=,",
+            "--assign",
+            "a=b",
+            "--file=prog.awk",
+            "--so",
+            "BEGIN { }",
+            "--trad",
+            "--non-decimal",
+            "input",
+        ])
+        .unwrap();
+        assert_eq!(options.field_separator.as_deref(), Some(","));
+        assert_eq!(
+            options.assignments,
+            vec![("a".to_string(), "b".to_string())]
+        );
+        assert_eq!(
+            options.sources,
+            vec![
+                ProgramSource::File("prog.awk".to_string()),
+                ProgramSource::Text("BEGIN { }".to_string())
+            ]
+        );
+        assert_eq!(options.dialect, Dialect::Traditional);
+        assert!(options.non_decimal_data);
+        assert_eq!(options.operands, vec!["input"]);
+    }
+
+    #[test]
+    fn test_field_separator_escapes() {
+        let fs = |arg: &str| parse(&[arg, "p"]).unwrap().field_separator.unwrap();
+        assert_eq!(fs("-Ft"), "\t");
+        assert_eq!(fs("-F\\t"), "\t");
+        assert_eq!(fs("-F\\|"), "|");
+        assert_eq!(fs("-Ftab"), "tab");
+        assert_eq!(fs("-F\\x41"), "A");
+        assert!(parse(&["-F\\x41", "--posix", "p"]).is_err());
+    }
+
+    #[test]
+    fn test_options_end_at_first_operand_or_dashes() {
+        let options = parse(&["--", "-v", "x"]).unwrap();
+        assert_eq!(options.sources, vec![ProgramSource::Text("-v".to_string())]);
+        assert_eq!(options.operands, vec!["x"]);
+
+        let options = parse(&["prog", "-v", "-"]).unwrap();
+        assert_eq!(options.operands, vec!["-v", "-"]);
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let err = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert_eq!(err(&["-z", "p"]), "invalid option -- 'z'");
+        assert_eq!(err(&["-f"]), "option requires an argument -- 'f'");
+        assert_eq!(err(&["--nope", "p"]), "unrecognized option '--nope'");
+        assert_eq!(
+            err(&["--posix=1", "p"]),
+            "option '--posix' doesn't allow an argument"
+        );
+        assert_eq!(err(&["--file"]), "option '--file' requires an argument");
+        assert!(err(&["-v", "1x=2", "p"]).contains("invalid variable assignment"));
+        assert_eq!(err(&[]), "no program text given");
+    }
+
+    #[test]
+    fn test_ambiguous_long_option() {
+        let err = parse(&["--fi", "p"]).unwrap_err();
+        assert_eq!(err.to_string(), "option '--fi' is ambiguous");
+    }
+}
@@ -0,0 +1,69 @@
+//! Language dialects selected with `--posix` and `--traditional`
+
+/// The AWK dialect a program is lexed, parsed and run in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// POSIX AWK plus gawk extensions (the default)
+    #[default]
+    Gawk,
+    /// Strict POSIX AWK (`--posix`)
+    Posix,
+    /// Traditional Unix AWK (`--traditional`)
+    Traditional,
+}
+
+/// gawk builtin functions that are unavailable outside [`Dialect::Gawk`]
+///
+/// In the other dialects these names are ordinary identifiers, so a program
+/// may define its own functions with them.
+pub const GAWK_FUNCTIONS: &[&str] = &[
+    "gensub", "patsplit", "asort", "asorti", "systime", "mktime", "strftime", "typeof", "isarray",
+    "and", "or", "xor", "compl", "lshift", "rshift", "intdiv", "strtonum",
+];
+
+/// gawk special variables that are ordinary variables outside [`Dialect::Gawk`]
+pub const GAWK_VARIABLES: &[&str] = &["FPAT", "FIELDWIDTHS", "IGNORECASE", "PROCINFO", "RT"];
+
+impl Dialect {
+    /// Whether gawk extensions are available
+    pub fn gawk_extensions(self) -> bool {
+        self == Dialect::Gawk
+    }
+
+    /// The command line option that selects this dialect
+    pub fn option(self) -> &'static str {
+        match self {
+            Dialect::Gawk => "default",
+            Dialect::Posix => "--posix",
+            Dialect::Traditional => "--traditional",
+        }
+    }
+
+    /// Diagnostic for using `extension` in this dialect
+    pub fn extension_error(self, extension: &str) -> String {
+        format!(
+            "{} is a gawk extension and is not allowed in {} mode",
+            extension,
+            self.option()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_error_names_mode() {
+        assert_eq!(
+            Dialect::Posix.extension_error("gensub()"),
+            "gensub() is a gawk extension and is not allowed in --posix mode"
+        );
+        assert!(
+            Dialect::Traditional
+                .extension_error("`|&'")
+                .contains("--traditional")
+        );
+        assert!(Dialect::default().gawk_extensions());
+    }
+}
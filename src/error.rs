@@ -1,21 +1,37 @@
 use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Location in source code for error reporting
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SourceLocation {
     pub line: usize,
     pub column: usize,
+    /// Source file name, shared by every location in the file; `None` for a
+    /// program given on the command line
+    pub file: Option<Arc<str>>,
 }
 
 impl SourceLocation {
     pub fn new(line: usize, column: usize) -> Self {
-        Self { line, column }
+        Self {
+            line,
+            column,
+            file: None,
+        }
+    }
+
+    /// The same location in the named source file
+    pub fn in_file(self, file: Option<Arc<str>>) -> Self {
+        Self { file, ..self }
     }
 }
 
 impl fmt::Display for SourceLocation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}, ", file)?;
+        }
         write!(f, "line {}, column {}", self.line, self.column)
     }
 }
@@ -66,6 +82,14 @@ impl Error {
         }
     }
 
+    /// Parser error at a token's location, including its source file
+    pub fn parser_in(message: impl Into<String>, location: SourceLocation) -> Self {
+        Self::Parser {
+            message: message.into(),
+            location,
+        }
+    }
+
     pub fn runtime(message: impl Into<String>) -> Self {
         Self::Runtime {
             message: message.into(),
@@ -78,6 +102,26 @@ impl Error {
             location: SourceLocation::new(line, column),
         }
     }
+
+    /// Runtime error at an AST node's location, including its source file
+    pub fn runtime_in(message: impl Into<String>, location: SourceLocation) -> Self {
+        Self::RuntimeWithLocation {
+            message: message.into(),
+            location,
+        }
+    }
+
+    /// Attach a source file name to this error's location if it has none
+    pub fn in_file(mut self, file: Option<Arc<str>>) -> Self {
+        if let Self::Lexer { location, .. }
+        | Self::Parser { location, .. }
+        | Self::RuntimeWithLocation { location, .. } = &mut self
+            && location.file.is_none()
+        {
+            location.file = file;
+        }
+        self
+    }
 }
 
 /// Result type alias for awk-rs operations
@@ -130,6 +174,24 @@ mod tests {
         assert!(msg.contains("line 5"));
     }
 
+    #[test]
+    fn test_location_with_file() {
+        let loc = SourceLocation::new(3, 7).in_file(Some("lib.awk".into()));
+        assert_eq!(format!("{}", loc), "lib.awk, line 3, column 7");
+
+        let err = Error::lexer("unexpected character", 3, 7).in_file(Some("lib.awk".into()));
+        assert!(err.to_string().contains("at lib.awk, line 3, column 7"));
+        let err = Error::parser_in("expected expression", loc).in_file(Some("other.awk".into()));
+        assert!(err.to_string().contains("at lib.awk, line 3"));
+
+        let loc = SourceLocation {
+            line: 2,
+            column: 1,
+            ..Default::default()
+        };
+        assert_eq!(loc, SourceLocation::new(2, 1));
+    }
+
     #[test]
     fn test_io_error() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
@@ -0,0 +1,199 @@
+//! AWK arrays, whose elements may themselves be arrays (gawk arrays of arrays)
+
+use std::collections::HashMap;
+
+use crate::value::Value;
+
+/// An associative array
+///
+/// Elements are kept in insertion order, so traversal without
+/// `PROCINFO["sorted_in"]` is the same on every run. Deleting an element
+/// leaves a hole that is reclaimed once holes outnumber the elements, which
+/// keeps deletion O(1) amortised.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AwkArray {
+    /// Elements in insertion order, `None` where one was deleted
+    entries: Vec<Option<(String, Element)>>,
+    /// Position of each element in `entries`
+    index: HashMap<String, usize>,
+}
+
+/// An array element: a scalar, or a subarray as in `a["x"]["y"]`
+#[derive(Debug, Clone)]
+pub(crate) enum Element {
+    Scalar(Value),
+    Array(AwkArray),
+}
+
+/// Where an array lives: a named array and the subscripts leading to a
+/// subarray of it (empty for the named array itself)
+///
+/// Function parameters bound to an array argument refer to it this way.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ArrayRef {
+    pub name: String,
+    pub path: Vec<String>,
+}
+
+impl AwkArray {
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Element> {
+        let &position = self.index.get(key)?;
+        self.entries[position].as_ref().map(|(_, element)| element)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Element> {
+        let &position = self.index.get(key)?;
+        self.entries[position].as_mut().map(|(_, element)| element)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Set a scalar element, replacing whatever was there
+    ///
+    /// A replaced element keeps its place in the order.
+    pub fn insert(&mut self, key: String, value: Value) {
+        self.insert_element(key, Element::Scalar(value));
+    }
+
+    /// Set an element, which may be a subarray
+    pub fn insert_element(&mut self, key: String, element: Element) {
+        if let Some(existing) = self.get_mut(&key) {
+            *existing = element;
+            return;
+        }
+        self.index.insert(key.clone(), self.entries.len());
+        self.entries.push(Some((key, element)));
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Element> {
+        let position = self.index.remove(key)?;
+        let (_, element) = self.entries[position].take()?;
+        if self.index.is_empty() {
+            self.entries.clear();
+        } else if self.entries.len() > 2 * self.index.len() {
+            self.compact();
+        }
+        Some(element)
+    }
+
+    /// Close up the holes left by deleted elements
+    fn compact(&mut self) {
+        self.entries.retain(Option::is_some);
+        for (position, (key, _)) in self.entries.iter().flatten().enumerate() {
+            if let Some(slot) = self.index.get_mut(key) {
+                *slot = position;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Element)> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|(key, element)| (key, element))
+    }
+
+    /// The subarray at `key`, created if the element does not exist
+    ///
+    /// Returns `None` if the element is a scalar.
+    pub fn subarray_mut(&mut self, key: &str) -> Option<&mut AwkArray> {
+        if !self.contains_key(key) {
+            self.insert_element(key.to_string(), Element::Array(AwkArray::default()));
+        }
+        match self.get_mut(key) {
+            Some(Element::Array(array)) => Some(array),
+            _ => None,
+        }
+    }
+}
+
+/// Storage name for a function's local array parameter
+///
+/// The `#` keeps it apart from every AWK identifier, and the call depth from
+/// the same parameter of other active calls.
+pub(crate) fn local_array_name(param: &str, depth: usize) -> String {
+    format!("{}#{}", param, depth)
+}
+
+/// How an array element is written in diagnostics, e.g. `a["x"]["y"]`
+pub(crate) fn element_name(name: &str, path: &[String]) -> String {
+    let mut text = name.to_string();
+    for key in path {
+        text.push_str(&format!("[\"{}\"]", key));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subarrays() {
+        let mut array = AwkArray::default();
+        array
+            .subarray_mut("x")
+            .unwrap()
+            .insert("y".to_string(), Value::Number(1.0));
+        array.insert("z".to_string(), Value::Number(2.0));
+
+        assert_eq!(array.len(), 2);
+        assert!(matches!(array.get("x"), Some(Element::Array(sub)) if sub.contains_key("y")));
+        assert!(array.subarray_mut("z").is_none());
+
+        array.remove("x");
+        assert!(!array.contains_key("x"));
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut array = AwkArray::default();
+        for key in ["b", "c", "a", "d"] {
+            array.insert(key.to_string(), Value::Number(1.0));
+        }
+        array.insert("c".to_string(), Value::Number(2.0));
+        array.remove("b");
+        array.insert("b".to_string(), Value::Number(3.0));
+        let keys: Vec<_> = array.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["c", "a", "d", "b"]);
+        assert!(matches!(array.get("c"), Some(Element::Scalar(Value::Number(n))) if *n == 2.0));
+
+        // Deleting most elements compacts the storage without losing order
+        for i in 0..100 {
+            array.insert(i.to_string(), Value::Number(i as f64));
+        }
+        for i in (0..100).filter(|i| i % 10 != 0) {
+            array.remove(&i.to_string());
+        }
+        assert!(array.entries.len() <= 2 * array.len());
+        let keys: Vec<_> = array.keys().map(String::as_str).collect();
+        assert_eq!(keys[..4], ["c", "a", "d", "b"]);
+        assert_eq!(
+            keys[4..],
+            ["0", "10", "20", "30", "40", "50", "60", "70", "80", "90"]
+        );
+        assert!(matches!(array.get("90"), Some(Element::Scalar(Value::Number(n))) if *n == 90.0));
+    }
+
+    #[test]
+    fn test_element_name() {
+        let path = vec!["x".to_string(), "1".to_string()];
+        assert_eq!(element_name("a", &path), r#"a["x"]["1"]"#);
+        assert_eq!(element_name("a", &[]), "a");
+    }
+}
@@ -1,10 +1,36 @@
 use std::io::Write;
 
 use crate::ast::Expr;
+use crate::dialect::GAWK_FUNCTIONS;
 use crate::error::{Error, Result, SourceLocation};
-use crate::value::Value;
+use crate::value::{Value, parse_leading_number, parse_non_decimal};
+use regex::Regex;
+
+use super::array::{ArrayRef, Element, local_array_name};
+use super::sort::{SortKey, SortOrder};
+use super::{Interpreter, at_location, is_stdout_name};
+
+/// Builtin functions that can be called by name, as with `@f()`
+///
+/// `getline` is left out: it is a statement-like form, not a function.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "length", "substr", "index", "split", "sub", "gsub", "match", "sprintf", "tolower", "toupper",
+    "sin", "cos", "atan2", "exp", "log", "sqrt", "int", "rand", "srand", "system", "close",
+    "fflush",
+];
+
+/// Whether `name` is a builtin function, counting gawk's
+pub(super) fn is_builtin_function(name: &str) -> bool {
+    BUILTIN_FUNCTIONS.contains(&name) || GAWK_FUNCTIONS.contains(&name)
+}
 
-use super::Interpreter;
+/// An evaluated argument of a user-defined function
+#[derive(Debug, Clone)]
+pub(super) enum Argument {
+    Value(Value),
+    /// An array or subarray, passed by reference
+    Array(ArrayRef),
+}
 
 impl<'a> Interpreter<'a> {
     /// Call a function with special handling for builtins that need AST access
@@ -15,60 +41,223 @@ impl<'a> Interpreter<'a> {
         location: SourceLocation,
         output: &mut W,
     ) -> Result<Value> {
+        // gawk's builtins are ordinary function names in the other dialects
+        let builtin = self.dialect().gawk_extensions() || !GAWK_FUNCTIONS.contains(&name);
+
         // Check for built-in functions that need special argument handling
-        match name {
-            "sub" | "gsub" => return self.call_regex_sub(name, args, location),
-            "match" => return self.call_match(args, location),
-            "split" => return self.call_split(args, location),
-            "patsplit" => return self.call_patsplit(args, location),
-            "asort" | "asorti" => return self.call_asort(name == "asorti", args, location),
-            "getline" => return self.call_getline(args, location),
-            "close" => return self.call_close(args, location),
-            "fflush" => return self.call_fflush(args, location, output),
-            _ => {}
+        let special = match name {
+            _ if !builtin => None,
+            "sub" | "gsub" => Some(self.call_regex_sub(name, args, location.clone())),
+            "match" => Some(self.call_match(args, location.clone())),
+            "split" => Some(self.call_split(args, location.clone())),
+            "patsplit" => Some(self.call_patsplit(args, location.clone())),
+            "asort" | "asorti" => {
+                Some(self.call_asort(name == "asorti", args, location.clone(), output))
+            }
+            "intdiv" => Some(self.call_intdiv(args, location.clone())),
+            "getline" => Some(self.call_getline(args, location.clone())),
+            "close" => Some(self.call_close(args, location.clone(), output)),
+            "fflush" => Some(self.call_fflush(args, location.clone(), output)),
+            "typeof" => Some(
+                self.call_typeof(args, location.clone())
+                    .map(|type_name| Value::from_string(type_name.to_string())),
+            ),
+            "isarray" => Some(
+                self.call_typeof(args, location.clone())
+                    .map(|type_name| Value::Number(if type_name == "array" { 1.0 } else { 0.0 })),
+            ),
+            "length" if args.len() == 1 => self.array_length(&args[0]).transpose(),
+            _ => None,
+        };
+        if let Some(result) = special {
+            return result.map_err(|e| at_location(e, &location));
         }
 
-        // Evaluate all arguments for other functions
-        let arg_values: Result<Vec<Value>> = args.iter().map(|e| self.eval_expr(e)).collect();
-        let arg_values = arg_values?;
+        // Evaluate all arguments for other functions; arrays are passed by reference
+        let arguments = args
+            .iter()
+            .map(|e| self.eval_argument(e))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| at_location(e, &location))?;
 
         // Check for other built-in functions
-        if let Some(result) = self.call_builtin(name, &arg_values)? {
-            return Ok(result);
+        if builtin {
+            let arg_values: Vec<Value> = arguments
+                .iter()
+                .map(|arg| match arg {
+                    Argument::Value(value) => value.clone(),
+                    Argument::Array(_) => Value::Uninitialized,
+                })
+                .collect();
+            let result = self
+                .call_builtin(name, &arg_values)
+                .map_err(|e| at_location(e, &location))?;
+            if let Some(result) = result {
+                return Ok(result);
+            }
         }
 
         // Check for user-defined functions
         if let Some(func) = self.functions.get(name).cloned() {
-            // Extract array names from arguments for pass-by-reference
-            let array_refs: Vec<Option<String>> = args
-                .iter()
-                .map(|e| {
-                    if let Expr::Var(name, _) = e
-                        && self.arrays.contains_key(name)
-                    {
-                        return Some(name.clone());
-                    }
-                    None
-                })
-                .collect();
-            return self.call_user_function(func, arg_values, array_refs, output);
+            return self.call_user_function(func, arguments, output);
         }
 
-        Err(Error::runtime_at(
+        if !builtin {
+            return Err(Error::runtime_in(
+                self.dialect().extension_error(&format!("{}()", name)),
+                location,
+            ));
+        }
+
+        Err(Error::runtime_in(
             format!("undefined function: {}", name),
-            location.line,
-            location.column,
+            location,
         ))
     }
 
+    /// Evaluate a function argument, passing arrays and subarrays by reference
+    fn eval_argument(&mut self, arg: &Expr) -> Result<Argument> {
+        match arg {
+            // Arrays, and untyped names that the function may make arrays,
+            // are passed by reference
+            Expr::Var(name, _) if self.is_array(name) || self.is_untyped(name) => {
+                Ok(Argument::Array(self.array_ref(name, Vec::new())))
+            }
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                ..
+            } => {
+                let (path, key) = self.eval_element(path, indices)?;
+                let element = self.lookup_array(array, &path)?.and_then(|a| a.get(&key));
+                if let Some(Element::Array(_)) = element {
+                    let mut path = path;
+                    path.push(key);
+                    Ok(Argument::Array(self.array_ref(array, path)))
+                } else {
+                    Ok(Argument::Value(self.get_array_element(array, &path, &key)?))
+                }
+            }
+            _ => Ok(Argument::Value(self.eval_expr(arg)?)),
+        }
+    }
+
+    /// Evaluate the subscripts of an array element to its subarray path and key
+    fn eval_element(
+        &mut self,
+        path: &[Vec<Expr>],
+        indices: &[Expr],
+    ) -> Result<(Vec<String>, String)> {
+        let mut null = std::io::sink();
+        let path = self.eval_path(path, &mut null)?;
+        let key_parts: Result<Vec<Value>> = indices.iter().map(|e| self.eval_expr(e)).collect();
+        Ok((path, self.make_array_key(&key_parts?)))
+    }
+
+    /// The array (or subarray) a builtin's array argument names
+    fn array_argument(&mut self, arg: &Expr) -> Result<Option<(String, Vec<String>)>> {
+        match arg {
+            Expr::Var(name, _) => Ok(Some((name.clone(), Vec::new()))),
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                ..
+            } => {
+                let (mut path, key) = self.eval_element(path, indices)?;
+                path.push(key);
+                Ok(Some((array.clone(), path)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// `length(a)` for an array or subarray; `None` if the argument is not one
+    fn array_length(&mut self, arg: &Expr) -> Result<Option<Value>> {
+        let length = match arg {
+            Expr::Var(name, _) if self.is_array(name) => {
+                self.lookup_array(name, &[])?.map(|a| a.len())
+            }
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                ..
+            } => {
+                // Evaluate the subscripts once, whatever the element turns out to be
+                let (path, key) = self.eval_element(path, indices)?;
+                let length = match self.lookup_array(array, &path)?.and_then(|a| a.get(&key)) {
+                    Some(Element::Array(sub)) => sub.len(),
+                    _ => self
+                        .get_array_element(array, &path, &key)?
+                        .to_string_val()
+                        .chars()
+                        .count(),
+                };
+                Some(length)
+            }
+            _ => None,
+        };
+        Ok(length.map(|n| Value::Number(n as f64)))
+    }
+
+    /// typeof(x): the type of the argument, inspected without forcing arrays
+    /// or untyped variables to be scalars
+    fn call_typeof(&mut self, args: &[Expr], location: SourceLocation) -> Result<&'static str> {
+        let [arg] = args else {
+            return Err(Error::runtime_in(
+                "typeof: expects exactly one argument",
+                location,
+            ));
+        };
+        let type_name = match arg {
+            Expr::Var(name, _) => {
+                if self.is_array(name) {
+                    "array"
+                } else {
+                    match self.get_variable(name) {
+                        Value::Uninitialized if !self.variables.contains_key(name) => "untyped",
+                        value => value.type_name(),
+                    }
+                }
+            }
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                ..
+            } => {
+                let (path, key) = self.eval_element(path, indices)?;
+                match self.lookup_array(array, &path)?.and_then(|a| a.get(&key)) {
+                    Some(Element::Array(_)) => "array",
+                    Some(Element::Scalar(value)) => value.type_name(),
+                    None => match self.get_array_element(array, &path, &key)? {
+                        Value::Uninitialized => "untyped",
+                        value => value.type_name(),
+                    },
+                }
+            }
+            other => self.eval_expr(other)?.type_name(),
+        };
+        Ok(type_name)
+    }
+
     /// Extract regex pattern from an expression (handles both regex literals and strings)
     fn extract_pattern(&mut self, expr: &Expr) -> Result<String> {
         match expr {
-            Expr::Regex(pattern, _) => Ok(pattern.clone()),
+            Expr::Regex(pattern, _) | Expr::TypedRegex(pattern, _) => Ok(pattern.clone()),
             other => Ok(self.eval_expr(other)?.to_string_val()),
         }
     }
 
+    /// Compile a regex for a builtin, reporting errors at the call site
+    fn builtin_regex(&mut self, pattern: &str, location: SourceLocation) -> Result<Regex> {
+        self.get_regex(pattern)
+            .cloned()
+            .map_err(|e| at_location(e, &location))
+    }
+
     /// Call sub or gsub with proper regex and target handling
     fn call_regex_sub(
         &mut self,
@@ -101,13 +290,7 @@ impl<'a> Interpreter<'a> {
             (self.record.clone(), None)
         };
 
-        let re = regex::Regex::new(&pattern).map_err(|e| {
-            Error::runtime_at(
-                format!("invalid regex: {}", e),
-                location.line,
-                location.column,
-            )
-        })?;
+        let re = self.builtin_regex(&pattern, location)?;
 
         let (new_str, count) = regex_sub_helper(&re, &replacement, &target_value, global);
 
@@ -136,13 +319,7 @@ impl<'a> Interpreter<'a> {
             .transpose()?
             .unwrap_or_default();
 
-        let re = regex::Regex::new(&pattern).map_err(|e| {
-            Error::runtime_at(
-                format!("invalid regex: {}", e),
-                location.line,
-                location.column,
-            )
-        })?;
+        let re = self.builtin_regex(&pattern, location)?;
 
         if let Some(m) = re.find(&s) {
             self.rstart = m.start() + 1;
@@ -164,23 +341,19 @@ impl<'a> Interpreter<'a> {
             .map(|v| v.to_string_val())
             .unwrap_or_default();
 
-        // Get array name from second argument (must be a variable name)
-        let array_name = match args.get(1) {
-            Some(Expr::Var(name, _)) => name.clone(),
-            Some(Expr::ArrayAccess { array, .. }) => array.clone(),
-            Some(_) => {
-                return Err(Error::runtime_at(
-                    "split: second argument must be an array",
-                    location.line,
-                    location.column,
-                ));
-            }
+        // Get the array from the second argument (an array or subarray)
+        let (array_name, path) = match args.get(1) {
+            Some(arg) => match self.array_argument(arg)? {
+                Some(array) => array,
+                None => {
+                    return Err(Error::runtime_in(
+                        "split: second argument must be an array",
+                        location,
+                    ));
+                }
+            },
             None => {
-                return Err(Error::runtime_at(
-                    "split: missing array argument",
-                    location.line,
-                    location.column,
-                ));
+                return Err(Error::runtime_in("split: missing array argument", location));
             }
         };
 
@@ -192,102 +365,148 @@ impl<'a> Interpreter<'a> {
         };
 
         // Clear the array
-        self.arrays.remove(&array_name);
+        self.clear_array(&array_name, &path)?;
 
         // Split and populate array
         let parts: Vec<&str> = if sep == " " {
             s.split_whitespace().collect()
-        } else if sep.len() == 1 {
+        } else if sep.len() == 1 && !(self.ignore_case() && sep.as_bytes()[0].is_ascii_alphabetic())
+        {
             s.split(&sep).collect()
         } else {
             // Use regex split for multi-char separators
-            let re = regex::Regex::new(&sep).map_err(|e| {
-                Error::runtime_at(
-                    format!("invalid regex: {}", e),
-                    location.line,
-                    location.column,
-                )
-            })?;
+            let re = self.builtin_regex(&sep, location)?;
             re.split(&s).collect()
         };
 
         for (i, part) in parts.iter().enumerate() {
             let key = (i + 1).to_string();
-            self.set_array_element(&array_name, &key, Value::from_string(part.to_string()));
+            let value = self.input_value(part.to_string());
+            self.set_array_element(&array_name, &path, &key, value)?;
         }
 
         Ok(Value::Number(parts.len() as f64))
     }
 
-    /// asort(source [, dest]) - sort array values
-    /// asorti(source [, dest]) - sort array indices
-    fn call_asort(
+    /// asort(source [, dest [, how]]) - sort array values
+    /// asorti(source [, dest [, how]]) - sort array indices
+    ///
+    /// `how` is a `PROCINFO["sorted_in"]` style order. By default values sort
+    /// numbers first, then strings, then subarrays, and indices sort as strings.
+    fn call_asort<W: Write>(
         &mut self,
         sort_indices: bool,
         args: &[Expr],
         location: SourceLocation,
+        output: &mut W,
     ) -> Result<Value> {
-        // Get source array name
-        let source_name = match args.first() {
-            Some(Expr::Var(name, _)) => name.clone(),
-            _ => {
-                return Err(Error::runtime_at(
-                    if sort_indices {
-                        "asorti: first argument must be an array"
-                    } else {
-                        "asort: first argument must be an array"
-                    },
-                    location.line,
-                    location.column,
-                ));
-            }
+        // Get source array
+        let source = match args.first() {
+            Some(arg) => self.array_argument(arg)?,
+            None => None,
+        };
+        let Some((source_name, source_path)) = source else {
+            return Err(Error::runtime_in(
+                if sort_indices {
+                    "asorti: first argument must be an array"
+                } else {
+                    "asort: first argument must be an array"
+                },
+                location,
+            ));
         };
 
-        // Get optional destination array name
-        let dest_name = match args.get(1) {
-            Some(Expr::Var(name, _)) => Some(name.clone()),
+        // Get optional destination array
+        let dest = match args.get(1) {
+            Some(arg) => match self.array_argument(arg)? {
+                Some(array) => Some(array),
+                None => {
+                    return Err(Error::runtime_in(
+                        if sort_indices {
+                            "asorti: second argument must be an array"
+                        } else {
+                            "asort: second argument must be an array"
+                        },
+                        location,
+                    ));
+                }
+            },
             None => None,
-            _ => {
-                return Err(Error::runtime_at(
-                    if sort_indices {
-                        "asorti: second argument must be an array"
-                    } else {
-                        "asort: second argument must be an array"
-                    },
-                    location.line,
-                    location.column,
-                ));
-            }
         };
 
-        // Get values to sort
-        let items: Vec<String> = if let Some(arr) = self.arrays.get(&source_name) {
-            if sort_indices {
-                arr.keys().cloned().collect()
-            } else {
-                arr.values().map(|v| v.to_string_val()).collect()
-            }
-        } else {
-            Vec::new()
+        let order = match args.get(2) {
+            Some(how) => SortOrder::from_name(&self.eval_expr(how)?.to_string_val()),
+            None => SortOrder::Predefined {
+                key: if sort_indices {
+                    SortKey::IndexString
+                } else {
+                    SortKey::ValueType
+                },
+                descending: false,
+            },
+        };
+        let keys = self.sorted_keys(&source_name, &source_path, &order, output)?;
+
+        // Values keep their type; subarrays are copied
+        let sorted: Vec<Element> = match self.lookup_array(&source_name, &source_path)? {
+            Some(_) if sort_indices => keys
+                .into_iter()
+                .map(|key| Element::Scalar(Value::from_input(key)))
+                .collect(),
+            Some(arr) => keys
+                .iter()
+                .filter_map(|key| arr.get(key).cloned())
+                .collect(),
+            None => Vec::new(),
         };
-
-        let mut sorted = items;
-        sorted.sort();
 
         let count = sorted.len();
 
         // Store in destination (or source if no dest)
-        let target = dest_name.unwrap_or_else(|| source_name.clone());
-        self.arrays.remove(&target);
-
-        for (i, item) in sorted.iter().enumerate() {
-            let key = (i + 1).to_string();
-            self.set_array_element(&target, &key, Value::from_string(item.clone()));
+        let (target, target_path) = dest.unwrap_or((source_name, source_path));
+        self.clear_array(&target, &target_path)?;
+        let target = self.lookup_array_mut(&target, &target_path)?;
+        for (i, element) in sorted.into_iter().enumerate() {
+            target.insert_element((i + 1).to_string(), element);
         }
 
         Ok(Value::Number(count as f64))
     }
 
+    /// intdiv(numerator, denominator, result) - integer division, storing
+    /// result["quotient"] and result["remainder"]
+    fn call_intdiv(&mut self, args: &[Expr], location: SourceLocation) -> Result<Value> {
+        let mut operands = [0.0; 2];
+        for (operand, arg) in operands.iter_mut().zip(args) {
+            *operand = self.eval_expr(arg)?.to_number().trunc();
+        }
+        let [numerator, denominator] = operands;
+
+        let result = match args.get(2) {
+            Some(arg) => self.array_argument(arg)?,
+            None => None,
+        };
+        let Some((array, path)) = result else {
+            return Err(Error::runtime_in(
+                "intdiv: third argument must be an array",
+                location,
+            ));
+        };
+        if denominator == 0.0 {
+            return Err(Error::runtime_in(
+                "intdiv: division by zero attempted",
+                location,
+            ));
+        }
+
+        self.clear_array(&array, &path)?;
+        let quotient = (numerator / denominator).trunc();
+        self.set_array_element(&array, &path, "quotient", Value::Number(quotient))?;
+        let remainder = numerator % denominator;
+        self.set_array_element(&array, &path, "remainder", Value::Number(remainder))?;
+        Ok(Value::Number(0.0))
+    }
+
     /// patsplit(string, array, fieldpat [, seps]) - split by pattern matches
     fn call_patsplit(&mut self, args: &[Expr], location: SourceLocation) -> Result<Value> {
         // Get string to split
@@ -298,40 +517,38 @@ impl<'a> Interpreter<'a> {
             .map(|v| v.to_string_val())
             .unwrap_or_default();
 
-        // Get array name
-        let array_name = match args.get(1) {
-            Some(Expr::Var(name, _)) => name.clone(),
-            _ => {
-                return Err(Error::runtime_at(
-                    "patsplit: second argument must be an array",
-                    location.line,
-                    location.column,
-                ));
-            }
+        // Get the array
+        let array = match args.get(1) {
+            Some(arg) => self.array_argument(arg)?,
+            None => None,
+        };
+        let Some((array_name, path)) = array else {
+            return Err(Error::runtime_in(
+                "patsplit: second argument must be an array",
+                location,
+            ));
         };
 
         // Get field pattern
         let fieldpat = if let Some(pat_expr) = args.get(2) {
             self.extract_pattern(pat_expr)?
         } else {
-            return Err(Error::runtime_at(
+            return Err(Error::runtime_in(
                 "patsplit: missing fieldpat argument",
-                location.line,
-                location.column,
+                location,
             ));
         };
 
         // Optional separator array
-        let seps_name = match args.get(3) {
-            Some(Expr::Var(name, _)) => Some(name.clone()),
+        let seps = match args.get(3) {
+            Some(arg) => self.array_argument(arg)?,
             None => None,
-            _ => None,
         };
 
         // Clear destination arrays
-        self.arrays.remove(&array_name);
-        if let Some(ref name) = seps_name {
-            self.arrays.remove(name);
+        self.clear_array(&array_name, &path)?;
+        if let Some((name, seps_path)) = &seps {
+            self.clear_array(name, seps_path)?;
         }
 
         // Compile regex and find all matches
@@ -341,25 +558,27 @@ impl<'a> Interpreter<'a> {
         // Store matches in array
         for (i, mat) in matches.iter().enumerate() {
             let key = (i + 1).to_string();
-            self.set_array_element(
-                &array_name,
-                &key,
-                Value::from_string(mat.as_str().to_string()),
-            );
+            let value = self.input_value(mat.as_str().to_string());
+            self.set_array_element(&array_name, &path, &key, value)?;
         }
 
         // Store separators if requested
-        if let Some(ref name) = seps_name {
+        if let Some((name, seps_path)) = &seps {
             let mut last_end = 0;
             for (i, mat) in matches.iter().enumerate() {
                 let sep = &s[last_end..mat.start()];
                 let key = i.to_string();
-                self.set_array_element(name, &key, Value::from_string(sep.to_string()));
+                self.set_array_element(name, seps_path, &key, Value::from_string(sep.to_string()))?;
                 last_end = mat.end();
             }
             // Final separator after last match
             let key = matches.len().to_string();
-            self.set_array_element(name, &key, Value::from_string(s[last_end..].to_string()));
+            self.set_array_element(
+                name,
+                seps_path,
+                &key,
+                Value::from_string(s[last_end..].to_string()),
+            )?;
         }
 
         Ok(Value::Number(matches.len() as f64))
@@ -367,16 +586,27 @@ impl<'a> Interpreter<'a> {
 
     /// Call getline with file/pipe/variable handling
     fn call_getline(&mut self, args: &[Expr], location: SourceLocation) -> Result<Value> {
-        // getline returns: 1 (success), 0 (EOF), -1 (error)
-        // For now, just return 0 (EOF) for unsupported cases
-        // TODO: Implement proper getline with file/pipe support
-        let _ = args;
-        let _ = location;
-        Ok(Value::Number(0.0))
+        // getline(var) reads the next record of the main input into var
+        let var = match args.first() {
+            None => None,
+            Some(Expr::Var(name, _)) => Some(name),
+            Some(_) => {
+                return Err(Error::runtime_in(
+                    "getline: argument must be a variable",
+                    location,
+                ));
+            }
+        };
+        self.eval_getline(var, None, location)
     }
 
     /// Call close to close a file or pipe
-    fn call_close(&mut self, args: &[Expr], location: SourceLocation) -> Result<Value> {
+    fn call_close<W: Write>(
+        &mut self,
+        args: &[Expr],
+        location: SourceLocation,
+        output: &mut W,
+    ) -> Result<Value> {
         let filename = args
             .first()
             .map(|e| self.eval_expr(e))
@@ -384,13 +614,53 @@ impl<'a> Interpreter<'a> {
             .map(|v| v.to_string_val())
             .unwrap_or_default();
 
-        // Remove from any of our file/pipe collections
-        let found = self.output_files.remove(&filename).is_some()
-            || self.input_files.remove(&filename).is_some()
-            || self.pipes.remove(&filename).is_some();
+        // close(cmd, "to") and close(cmd, "from") close one side of a
+        // coprocess; the command is waited for once both are closed
+        let how = args
+            .get(1)
+            .map(|e| self.eval_expr(e))
+            .transpose()?
+            .map(|v| v.to_string_val().to_lowercase());
+        if let Some(how) = how {
+            if how != "to" && how != "from" {
+                return Err(Error::runtime_in(
+                    format!("close: `{}' is not a valid second argument", how),
+                    location,
+                ));
+            }
+            let Some(coprocess) = self.coprocesses.get_mut(&filename) else {
+                return Ok(Value::Number(-1.0));
+            };
+            if how == "to" {
+                coprocess.close_write();
+            } else {
+                coprocess.close_read();
+            }
+            if !coprocess.is_closed() {
+                return Ok(Value::Number(0.0));
+            }
+            let coprocess = self.coprocesses.remove(&filename).unwrap();
+            return Ok(Value::Number(coprocess.close()));
+        }
+
+        // Pipes and coprocesses report the command's exit status
+        let status = if let Some(file) = self.output_files.remove(&filename) {
+            file.close()
+        } else if self.input_files.remove(&filename).is_some() {
+            0.0
+        } else if let Some(pipe) = self.pipes.remove(&filename) {
+            pipe.close()
+        } else if let Some(coprocess) = self.coprocesses.remove(&filename) {
+            coprocess.close()
+        } else if is_stdout_name(&filename) {
+            // Standard output is never really closed, only flushed
+            output.flush().map_err(Error::Io)?;
+            0.0
+        } else {
+            -1.0
+        };
 
-        let _ = location;
-        Ok(Value::Number(if found { 0.0 } else { -1.0 }))
+        Ok(Value::Number(status))
     }
 
     /// Call fflush to flush output
@@ -409,7 +679,7 @@ impl<'a> Interpreter<'a> {
             Ok(Value::Number(0.0))
         } else {
             let filename = self.eval_expr(&args[0])?.to_string_val();
-            if filename.is_empty() {
+            if filename.is_empty() || is_stdout_name(&filename) {
                 output.flush().map_err(Error::Io)?;
                 Ok(Value::Number(0.0))
             } else if let Some(file) = self.output_files.get_mut(&filename) {
@@ -451,14 +721,17 @@ impl<'a> Interpreter<'a> {
             "index" => {
                 let s = args.first().map(|v| v.to_string_val()).unwrap_or_default();
                 let target = args.get(1).map(|v| v.to_string_val()).unwrap_or_default();
-                // Find byte position, then convert to character position
-                let pos = s
-                    .find(&target)
-                    .map(|byte_idx| {
-                        // Count characters before the byte index
-                        s[..byte_idx].chars().count() + 1
-                    })
-                    .unwrap_or(0);
+                let pos = if self.ignore_case() {
+                    index_ignore_case(&s, &target)
+                } else {
+                    // Find byte position, then convert to character position
+                    s.find(&target)
+                        .map(|byte_idx| {
+                            // Count characters before the byte index
+                            s[..byte_idx].chars().count() + 1
+                        })
+                        .unwrap_or(0)
+                };
                 Ok(Some(Value::Number(pos as f64)))
             }
 
@@ -653,15 +926,64 @@ impl<'a> Interpreter<'a> {
                 Ok(Some(Value::from_string(result)))
             }
 
+            // Bit manipulation functions
+            "and" | "or" | "xor" => {
+                if args.len() < 2 {
+                    return Err(Error::runtime(format!(
+                        "{}: called with less than two arguments",
+                        name
+                    )));
+                }
+                let mut result = bit_operand(name, 1, &args[0])?;
+                for (i, arg) in args.iter().enumerate().skip(1) {
+                    let operand = bit_operand(name, i + 1, arg)?;
+                    result = match name {
+                        "and" => result & operand,
+                        "or" => result | operand,
+                        _ => result ^ operand,
+                    };
+                }
+                Ok(Some(bit_result(result)))
+            }
+
+            "compl" => {
+                let n = bit_operand(name, 1, args.first().unwrap_or(&Value::Uninitialized))?;
+                Ok(Some(bit_result(!n)))
+            }
+
+            "lshift" | "rshift" => {
+                let n = bit_operand(name, 1, args.first().unwrap_or(&Value::Uninitialized))?;
+                let shift = bit_operand(name, 2, args.get(1).unwrap_or(&Value::Uninitialized))?;
+                let shifted = match (name, u32::try_from(shift)) {
+                    ("lshift", Ok(shift)) => n.checked_shl(shift),
+                    (_, Ok(shift)) => n.checked_shr(shift),
+                    (_, Err(_)) => None,
+                };
+                Ok(Some(bit_result(shifted.unwrap_or(0))))
+            }
+
+            "strtonum" => {
+                let n = match args.first() {
+                    Some(Value::String(s) | Value::Regex(s)) => {
+                        parse_non_decimal(s).unwrap_or_else(|| parse_leading_number(s))
+                    }
+                    // Input data such as fields keeps its text, so `017` read
+                    // from a file is octal here too
+                    Some(Value::NumericString(s, n)) => parse_non_decimal(s).unwrap_or(*n),
+                    Some(value) => value.to_number(),
+                    None => 0.0,
+                };
+                Ok(Some(Value::Number(n)))
+            }
+
             _ => Ok(None), // Not a built-in
         }
     }
 
-    fn call_user_function<W: Write>(
+    pub(super) fn call_user_function<W: Write>(
         &mut self,
         func: &crate::ast::FunctionDef,
-        args: Vec<Value>,
-        array_refs: Vec<Option<String>>,
+        args: Vec<Argument>,
         output: &mut W,
     ) -> Result<Value> {
         // Save current variables for local scope
@@ -671,49 +993,50 @@ impl<'a> Interpreter<'a> {
             .filter_map(|name| self.variables.get(name).map(|v| (name.clone(), v.clone())))
             .collect();
 
-        // Save any arrays that share names with parameters (for local arrays)
-        let saved_arrays: std::collections::HashMap<
-            String,
-            std::collections::HashMap<String, Value>,
-        > = func
-            .params
-            .iter()
-            .filter_map(|name| self.arrays.get(name).map(|a| (name.clone(), a.clone())))
-            .collect();
-
-        // Create array aliases for pass-by-reference
-        // If an argument is an array reference, the parameter name should point to the same array
-        let mut array_aliases: std::collections::HashMap<String, String> =
-            std::collections::HashMap::new();
-        for (i, param) in func.params.iter().enumerate() {
-            if let Some(Some(array_name)) = array_refs.get(i) {
-                // This parameter is an array reference
-                // Create an alias: when we access param, we should access array_name
-                if param != array_name {
-                    array_aliases.insert(param.clone(), array_name.clone());
+        // Every parameter names an array: the one passed for it, or one local
+        // to this call
+        self.call_depth += 1;
+        let mut array_aliases = std::collections::HashMap::new();
+        let mut scalars = Vec::new();
+        let mut args = args.into_iter();
+        for param in &func.params {
+            let array = match args.next() {
+                Some(Argument::Array(array)) => {
+                    self.variables.remove(param);
+                    array
                 }
-            }
+                arg => {
+                    if let Some(Argument::Value(value)) = arg {
+                        scalars.push((param, value));
+                    } else {
+                        // A missing argument leaves the parameter untyped
+                        self.variables.remove(param);
+                    }
+                    ArrayRef {
+                        name: local_array_name(param, self.call_depth),
+                        path: Vec::new(),
+                    }
+                }
+            };
+            array_aliases.insert(param.clone(), array);
         }
-        self.array_aliases = array_aliases;
+        let saved_aliases = std::mem::replace(&mut self.array_aliases, array_aliases);
 
         // Set scalar parameters
-        for (i, param) in func.params.iter().enumerate() {
-            // Skip if this is an array reference
-            if let Some(Some(_)) = array_refs.get(i) {
-                continue;
-            }
-            let value = args.get(i).cloned().unwrap_or(Value::Uninitialized);
+        for (param, value) in scalars {
             self.set_variable_value(param, value);
         }
 
         // Execute function body, passing the actual output
-        let result = match self.execute_block(&func.body, output)? {
-            super::stmt::StmtResult::Return(v) => v,
-            _ => Value::Uninitialized,
-        };
+        let result = self.execute_block(&func.body, output);
 
-        // Clear array aliases
-        self.array_aliases.clear();
+        // Drop local arrays and restore the caller's array parameters
+        for param in &func.params {
+            self.arrays
+                .remove(&local_array_name(param, self.call_depth));
+        }
+        self.call_depth -= 1;
+        self.array_aliases = saved_aliases;
 
         // Restore saved variables and remove parameters that weren't saved
         for param in &func.params {
@@ -724,25 +1047,10 @@ impl<'a> Interpreter<'a> {
             }
         }
 
-        // Restore any saved arrays or remove local arrays
-        for param in &func.params {
-            if let Some(arr) = saved_arrays.get(param) {
-                self.arrays.insert(param.clone(), arr.clone());
-            } else if !array_refs
-                .get(
-                    func.params
-                        .iter()
-                        .position(|p| p == param)
-                        .unwrap_or(usize::MAX),
-                )
-                .map(|r| r.is_some())
-                .unwrap_or(false)
-            {
-                self.arrays.remove(param);
-            }
-        }
-
-        Ok(result)
+        Ok(match result? {
+            super::stmt::StmtResult::Return(v) => v,
+            _ => Value::Uninitialized,
+        })
     }
 
     /// Generate a random number between 0 and 1 using xorshift64
@@ -756,6 +1064,28 @@ impl<'a> Interpreter<'a> {
     }
 }
 
+/// Largest integer a number holds exactly; gawk cuts bit operation results
+/// to this many bits
+const MAX_EXACT_INTEGER: u64 = (1 << 53) - 1;
+
+/// An operand of a bit manipulation function, truncated to an integer
+fn bit_operand(function: &str, position: usize, value: &Value) -> Result<u64> {
+    let n = value.to_number().trunc();
+    if n < 0.0 {
+        return Err(Error::runtime(format!(
+            "{}: argument {} negative value {} is not allowed",
+            function,
+            position,
+            Value::Number(n).to_string_val()
+        )));
+    }
+    Ok(n as u64)
+}
+
+fn bit_result(n: u64) -> Value {
+    Value::Number((n & MAX_EXACT_INTEGER) as f64)
+}
+
 fn regex_sub_helper(
     re: &regex::Regex,
     replacement: &str,
@@ -780,6 +1110,24 @@ fn regex_sub_helper(
     }
 }
 
+/// index() under IGNORECASE: 1-based character position of `target`, or 0
+fn index_ignore_case(s: &str, target: &str) -> usize {
+    let fold = |c: char| c.to_lowercase();
+    let target: Vec<char> = target.chars().collect();
+    let chars: Vec<char> = s.chars().collect();
+    if target.len() > chars.len() {
+        return 0;
+    }
+    (0..=chars.len() - target.len())
+        .find(|&i| {
+            chars[i..i + target.len()]
+                .iter()
+                .zip(&target)
+                .all(|(&a, &b)| fold(a).eq(fold(b)))
+        })
+        .map_or(0, |i| i + 1)
+}
+
 /// Simplified mktime implementation (UTC-based)
 fn simple_mktime(year: i64, month: i64, day: i64, hour: i64, min: i64, sec: i64) -> i64 {
     // Days in each month (non-leap year)
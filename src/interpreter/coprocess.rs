@@ -0,0 +1,130 @@
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::error::{Error, Result};
+
+use super::record::RecordReader;
+use super::wait_status;
+
+/// Two-way pipe to a command started with `|&` (gawk extension)
+///
+/// The command's output is drained by a background thread, so writing to the
+/// coprocess can never deadlock on a full output pipe that nobody is reading.
+pub struct Coprocess {
+    child: Child,
+    /// Write side, `None` once closed with `close(cmd, "to")`
+    stdin: Option<ChildStdin>,
+    /// Read side, `None` once closed with `close(cmd, "from")`
+    reader: Option<RecordReader<BufReader<ChannelReader>>>,
+}
+
+impl Coprocess {
+    pub(crate) fn spawn(cmd: &str) -> Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(Error::Io)?;
+
+        let stdin = child.stdin.take();
+        let mut stdout = child.stdout.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match stdout.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            reader: Some(RecordReader::new(BufReader::new(ChannelReader {
+                rx,
+                chunk: Vec::new(),
+                pos: 0,
+            }))),
+        })
+    }
+
+    /// The write side of the pipe
+    pub(crate) fn writer(&mut self) -> Result<&mut ChildStdin> {
+        self.stdin
+            .as_mut()
+            .ok_or_else(|| Error::runtime("attempt to write to closed write end of two-way pipe"))
+    }
+
+    /// The read side of the pipe
+    pub(crate) fn reader(&mut self) -> Result<&mut RecordReader<BufReader<ChannelReader>>> {
+        self.reader
+            .as_mut()
+            .ok_or_else(|| Error::runtime("attempt to read from closed read end of two-way pipe"))
+    }
+
+    /// Close the write side so the command sees end of file
+    pub(crate) fn close_write(&mut self) {
+        self.stdin = None;
+    }
+
+    /// Stop reading the command's output, leaving it running and writable
+    pub(crate) fn close_read(&mut self) {
+        self.reader = None;
+    }
+
+    /// Whether both sides have been closed, so only the command is left
+    pub(crate) fn is_closed(&self) -> bool {
+        self.stdin.is_none() && self.reader.is_none()
+    }
+
+    /// Close both sides and wait for the command, returning close()'s result
+    pub(crate) fn close(self) -> f64 {
+        let Coprocess {
+            mut child,
+            stdin,
+            reader,
+        } = self;
+        drop(stdin);
+        drop(reader);
+        wait_status(&mut child)
+    }
+}
+
+/// Reads the chunks of coprocess output sent by the draining thread
+pub struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // The command closed its output
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,494 @@
+use regex::Regex;
+
+use crate::dialect::Dialect;
+use crate::error::{Error, Result};
+
+/// Compile an AWK extended regular expression
+///
+/// Errors name the original AWK pattern rather than the translated one.
+/// `ignore_case` implements gawk's IGNORECASE.
+pub(crate) fn compile(pattern: &str, dialect: Dialect, ignore_case: bool) -> Result<Regex> {
+    let mut translated = translate(pattern, dialect)?;
+    if ignore_case {
+        translated.insert_str(0, "(?i)");
+    }
+    Regex::new(&translated).map_err(|e| invalid(pattern, e.to_string()))
+}
+
+/// Translate an AWK extended regular expression into `regex` crate syntax
+///
+/// This covers the places where the two dialects differ: bracket expressions
+/// (`[]a]`, `[a\]]`, character, collating and equivalence classes), AWK escape
+/// sequences such as `\/` and `\y`, braces that do not form an interval,
+/// repetition operators with nothing to repeat, and `.` matching newline.
+/// Outside the gawk dialect the GNU operators (`\y`, `\<`, `\s`, ...) and `\x`
+/// escapes stand for the plain character, and in traditional mode `{` is
+/// always literal.
+pub(crate) fn translate(pattern: &str, dialect: Dialect) -> Result<String> {
+    Translator {
+        pattern,
+        chars: pattern.chars().collect(),
+        pos: 0,
+        out: String::from("(?s)"),
+        intervals: dialect != Dialect::Traditional,
+        gnu: dialect.gawk_extensions(),
+    }
+    .run()
+}
+
+fn invalid(pattern: &str, message: impl Into<String>) -> Error {
+    Error::runtime(format!("invalid regex /{}/: {}", pattern, message.into()))
+}
+
+/// One element of a bracket expression
+enum ClassItem {
+    Char(char),
+    /// A `[:name:]` character class, already in regex crate syntax
+    Class(String),
+}
+
+struct Translator<'p> {
+    pattern: &'p str,
+    chars: Vec<char>,
+    pos: usize,
+    out: String,
+    intervals: bool,
+    /// GNU regex operators and `\x` escapes are recognised
+    gnu: bool,
+}
+
+impl Translator<'_> {
+    fn run(mut self) -> Result<String> {
+        // Start (in `out`) of the last item a repetition operator may apply to
+        let mut atom: Option<usize> = None;
+        // Whether that item already carries a repetition operator
+        let mut repeated = false;
+        // Start of each open group
+        let mut groups: Vec<usize> = Vec::new();
+
+        while let Some(c) = self.next() {
+            let start = self.out.len();
+            match c {
+                '\\' => {
+                    let is_atom = self.escape()?;
+                    atom = is_atom.then_some(start);
+                    repeated = false;
+                }
+                '[' => {
+                    self.bracket()?;
+                    atom = Some(start);
+                    repeated = false;
+                }
+                '(' => {
+                    groups.push(start);
+                    self.out.push('(');
+                    atom = None;
+                }
+                ')' => {
+                    let Some(open) = groups.pop() else {
+                        return Err(self.error("unmatched )"));
+                    };
+                    self.out.push(')');
+                    atom = Some(open);
+                    repeated = false;
+                }
+                '|' | '^' | '$' => {
+                    self.out.push(c);
+                    atom = None;
+                }
+                '*' | '+' | '?' => match atom {
+                    Some(atom_start) => {
+                        self.repeat(atom_start, repeated, &c.to_string());
+                        repeated = true;
+                    }
+                    // Nothing to repeat: the operator is literal
+                    None => {
+                        self.literal(c);
+                        atom = Some(start);
+                        repeated = false;
+                    }
+                },
+                '{' => match (atom, self.interval_after(atom)) {
+                    (Some(atom_start), Some(interval)) => {
+                        self.repeat(atom_start, repeated, &interval?);
+                        repeated = true;
+                    }
+                    _ => {
+                        self.literal('{');
+                        atom = Some(start);
+                        repeated = false;
+                    }
+                },
+                '.' => {
+                    self.out.push('.');
+                    atom = Some(start);
+                    repeated = false;
+                }
+                _ => {
+                    self.literal(c);
+                    atom = Some(start);
+                    repeated = false;
+                }
+            }
+        }
+
+        if !groups.is_empty() {
+            return Err(self.error("unmatched ("));
+        }
+        Ok(self.out)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        invalid(self.pattern, message)
+    }
+
+    fn literal(&mut self, c: char) {
+        self.out
+            .push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+    }
+
+    /// Apply a repetition operator to the item starting at `atom_start`
+    ///
+    /// A second operator on the same item (`a+*`) repeats the whole repetition,
+    /// as in POSIX, instead of turning it lazy as the regex crate would.
+    fn repeat(&mut self, atom_start: usize, repeated: bool, op: &str) {
+        if repeated {
+            self.out.insert_str(atom_start, "(?:");
+            self.out.push(')');
+        }
+        self.out.push_str(op);
+    }
+
+    /// Parse an interval expression after `{` if one is allowed here
+    ///
+    /// Returns `None`, consuming nothing, if the brace is a literal.
+    fn interval_after(&mut self, atom: Option<usize>) -> Option<Result<String>> {
+        if !self.intervals || atom.is_none() {
+            return None;
+        }
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let close = rest.find('}')?;
+        let body = &rest[..close];
+        let (min, max) = match body.split_once(',') {
+            Some((min, max)) => (min, Some(max)),
+            None => (body, None),
+        };
+        let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if !digits(min)
+            || !max.is_none_or(digits)
+            || (min.is_empty() && max.is_none_or(str::is_empty))
+        {
+            return None;
+        }
+        self.pos += body.chars().count() + 1;
+
+        let parse = |s: &str| s.parse::<u32>().ok();
+        let min_value = if min.is_empty() { Some(0) } else { parse(min) };
+        let result = match (min_value, max) {
+            (None, _) => Err(self.error("interval count too large")),
+            (Some(min), None) => Ok(format!("{{{}}}", min)),
+            (Some(min), Some("")) => Ok(format!("{{{},}}", min)),
+            (Some(min), Some(max)) => match parse(max) {
+                Some(max) if max >= min => Ok(format!("{{{},{}}}", min, max)),
+                Some(_) => Err(self.error("invalid interval: minimum is larger than maximum")),
+                None => Err(self.error("interval count too large")),
+            },
+        };
+        Some(result)
+    }
+
+    /// Translate an escape sequence outside brackets
+    ///
+    /// Returns whether the result can be repeated (assertions cannot).
+    fn escape(&mut self) -> Result<bool> {
+        let Some(c) = self.next() else {
+            return Err(self.error("trailing backslash"));
+        };
+        let assertion = match c {
+            _ if !self.gnu && "yB<>`'sSwWx".contains(c) => {
+                self.literal(c);
+                return Ok(true);
+            }
+            'y' => "\\b",
+            'B' => "\\B",
+            '<' => "\\b{start}",
+            '>' => "\\b{end}",
+            '`' => "\\A",
+            '\'' => "\\z",
+            's' | 'S' | 'w' | 'W' => {
+                self.out.push('\\');
+                self.out.push(c);
+                return Ok(true);
+            }
+            _ => {
+                let ch = self.escaped_char(c);
+                self.literal(ch);
+                return Ok(true);
+            }
+        };
+        self.out.push_str(assertion);
+        Ok(false)
+    }
+
+    /// The character denoted by `\c`, reading any further hex/octal digits
+    fn escaped_char(&mut self, c: char) -> char {
+        match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'f' => '\x0C',
+            'v' => '\x0B',
+            'a' => '\x07',
+            'b' => '\x08',
+            'x' => {
+                let mut value = 0u32;
+                let mut count = 0;
+                while count < 2
+                    && let Some(d) = self.peek().and_then(|d| d.to_digit(16))
+                {
+                    value = value * 16 + d;
+                    self.pos += 1;
+                    count += 1;
+                }
+                if count == 0 {
+                    'x'
+                } else {
+                    char::from_u32(value).unwrap_or('x')
+                }
+            }
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap();
+                let mut count = 1;
+                while count < 3
+                    && let Some(d) = self.peek().and_then(|d| d.to_digit(8))
+                {
+                    value = value * 8 + d;
+                    self.pos += 1;
+                    count += 1;
+                }
+                char::from_u32(value).unwrap_or('0')
+            }
+            // Anything else (\/, \", \., \\ ...) stands for itself
+            other => other,
+        }
+    }
+
+    /// Translate a bracket expression; the opening `[` has been consumed
+    fn bracket(&mut self) -> Result<()> {
+        let mut class = String::from("[");
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            class.push('^');
+        }
+
+        let mut first = true;
+        loop {
+            let Some(c) = self.next() else {
+                return Err(self.error("unterminated bracket expression"));
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            match self.class_item(c)? {
+                ClassItem::Class(name) => class.push_str(&name),
+                ClassItem::Char(lo) => {
+                    // A '-' between two characters forms a range
+                    let is_range = self.peek() == Some('-')
+                        && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']');
+                    if !is_range {
+                        class.push_str(&regex::escape(lo.encode_utf8(&mut [0; 4])));
+                        continue;
+                    }
+                    self.pos += 1;
+                    let end = self.next().unwrap();
+                    let ClassItem::Char(hi) = self.class_item(end)? else {
+                        return Err(self.error("invalid range end"));
+                    };
+                    if hi < lo {
+                        return Err(self.error(format!("invalid range {}-{}", lo, hi)));
+                    }
+                    class.push_str(&regex::escape(lo.encode_utf8(&mut [0; 4])));
+                    class.push('-');
+                    class.push_str(&regex::escape(hi.encode_utf8(&mut [0; 4])));
+                }
+            }
+        }
+
+        class.push(']');
+        self.out.push_str(&class);
+        Ok(())
+    }
+
+    /// Read one element of a bracket expression starting with `c`
+    fn class_item(&mut self, c: char) -> Result<ClassItem> {
+        match (c, self.peek()) {
+            ('[', Some(kind @ (':' | '.' | '='))) => {
+                self.pos += 1;
+                let rest: String = self.chars[self.pos..].iter().collect();
+                let terminator = format!("{}]", kind);
+                let Some(end) = rest.find(&terminator) else {
+                    return Err(self.error(format!("unterminated [{}", kind)));
+                };
+                let name = &rest[..end];
+                self.pos += name.chars().count() + 2;
+
+                if kind == ':' {
+                    const CLASSES: [&str; 12] = [
+                        "alnum", "alpha", "blank", "cntrl", "digit", "graph", "lower", "print",
+                        "punct", "space", "upper", "xdigit",
+                    ];
+                    if !CLASSES.contains(&name) {
+                        return Err(self.error(format!("invalid character class [:{}:]", name)));
+                    }
+                    return Ok(ClassItem::Class(format!("[:{}:]", name)));
+                }
+
+                // Collating symbols and equivalence classes are only supported
+                // for single characters, which stand for themselves
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => Ok(ClassItem::Char(ch)),
+                    _ => Err(self.error(format!(
+                        "unsupported collating element [{}{}{}]",
+                        kind, name, kind
+                    ))),
+                }
+            }
+            ('\\', Some(_)) => {
+                let escaped = self.next().unwrap();
+                if escaped == 'x' && !self.gnu {
+                    return Ok(ClassItem::Char('x'));
+                }
+                Ok(ClassItem::Char(self.escaped_char(escaped)))
+            }
+            _ => Ok(ClassItem::Char(c)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        compile(pattern, Dialect::Gawk, false)
+            .unwrap()
+            .is_match(text)
+    }
+
+    #[test]
+    fn test_plain_patterns() {
+        assert!(matches("a+b", "xaab"));
+        assert!(matches("^(foo|bar)$", "bar"));
+        assert!(!matches("^(foo|bar)$", "baz"));
+        assert!(matches("a.c", "a\nc"));
+    }
+
+    #[test]
+    fn test_intervals() {
+        assert!(matches("^a{2}$", "aa"));
+        assert!(!matches("^a{2}$", "aaa"));
+        assert!(matches("^a{2,}$", "aaaa"));
+        assert!(matches("^a{1,2}$", "a"));
+        assert!(matches("^(ab){2}$", "abab"));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let re = compile("^ab[c-e]$", Dialect::Gawk, true).unwrap();
+        assert!(re.is_match("AbD"));
+        assert!(
+            !compile("^ab$", Dialect::Gawk, false)
+                .unwrap()
+                .is_match("AB")
+        );
+    }
+
+    #[test]
+    fn test_posix_has_no_gnu_operators() {
+        let re = compile("\\yab\\x41", Dialect::Posix, false).unwrap();
+        assert!(re.is_match("yabx41"));
+        assert!(!re.is_match("ab A"));
+        assert!(
+            compile("^a{2}$", Dialect::Posix, false)
+                .unwrap()
+                .is_match("aa")
+        );
+    }
+
+    #[test]
+    fn test_traditional_braces_are_literal() {
+        let re = compile("^a{2}$", Dialect::Traditional, false).unwrap();
+        assert!(re.is_match("a{2}"));
+        assert!(!re.is_match("aa"));
+    }
+
+    #[test]
+    fn test_literal_braces() {
+        assert!(matches("{", "{"));
+        assert!(matches("^a{b}$", "a{b}"));
+        assert!(matches("x{,", "x{,"));
+        assert!(matches("^}$", "}"));
+    }
+
+    #[test]
+    fn test_awk_escapes() {
+        assert!(matches("a\\/b", "a/b"));
+        assert!(matches("\\.", "."));
+        assert!(!matches("\\.", "x"));
+        assert!(matches("\\yfoo\\y", "a foo b"));
+        assert!(!matches("\\yfoo\\y", "afoob"));
+        assert!(matches("\\<x", "a x"));
+        assert!(matches("\\x41\\101", "AA"));
+    }
+
+    #[test]
+    fn test_bracket_expressions() {
+        assert!(matches("^[]a]+$", "]a]"));
+        assert!(matches("^[a\\]]+$", "a]"));
+        assert!(matches("^[^]a]$", "b"));
+        assert!(!matches("^[^]a]$", "]"));
+        assert!(matches("^[a-]+$", "a-"));
+        assert!(matches("^[[:digit:][:upper:]]+$", "1A2"));
+        assert!(matches("^[[.-.]a]+$", "-a"));
+        assert!(matches("^[[=e=]]$", "e"));
+        assert!(matches("^[&~]+$", "&~"));
+    }
+
+    #[test]
+    fn test_nothing_to_repeat() {
+        assert!(matches("^*a", "*a"));
+        assert!(matches("(+)", "+"));
+        assert!(matches("^a+*$", "aaa"));
+        assert!(matches("^a*?$", ""));
+    }
+
+    #[test]
+    fn test_errors_name_original_pattern() {
+        let err = compile("[abc", Dialect::Gawk, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("/[abc/"), "{}", err);
+        let err = compile("[[:nope:]]", Dialect::Gawk, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("[:nope:]"), "{}", err);
+        assert!(compile("a{3,1}", Dialect::Gawk, false).is_err());
+        assert!(compile("(a", Dialect::Gawk, false).is_err());
+        assert!(compile("a\\", Dialect::Gawk, false).is_err());
+    }
+}
@@ -1,10 +1,13 @@
 use std::io::Write;
 
 use crate::ast::*;
-use crate::error::Result;
-use crate::value::{Value, compare_values};
+use crate::error::{Error, Result};
+use crate::value::Value;
 
-use super::Interpreter;
+use super::builtins::is_builtin_function;
+use super::coprocess::Coprocess;
+use super::record::{RecordReader, RecordSource};
+use super::{Interpreter, array_as_scalar, at_location, is_stdin_name, open_fd_name};
 
 impl<'a> Interpreter<'a> {
     /// Evaluate an expression (for contexts where we don't have output, like condition checking)
@@ -32,20 +35,42 @@ impl<'a> Interpreter<'a> {
                 Ok(Value::Number(if re.is_match(&record) { 1.0 } else { 0.0 }))
             }
 
-            Expr::Var(name, _) => Ok(self.get_variable(name)),
+            // A typed regexp used as a value is its text
+            Expr::TypedRegex(pattern, _) => Ok(Value::Regex(pattern.clone())),
+
+            Expr::Var(name, location) => match self.get_variable(name) {
+                // An unset name may be an array, which has no scalar value
+                Value::Uninitialized if self.is_array(name) => Err(Error::runtime_in(
+                    array_as_scalar(name, &[]),
+                    location.clone(),
+                )),
+                // Using an untyped name as a value makes it an unassigned scalar
+                Value::Uninitialized if !self.variables.contains_key(name) => {
+                    self.variables.insert(name.clone(), Value::Uninitialized);
+                    Ok(Value::Uninitialized)
+                }
+                value => Ok(value),
+            },
 
             Expr::Field(expr, _) => {
                 let index = self.eval_expr_with_output(expr, output)?.to_number() as usize;
-                Ok(Value::from_string(self.get_field(index)))
+                Ok(self.input_value(self.get_field(index)))
             }
 
-            Expr::ArrayAccess { array, indices, .. } => {
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                location,
+            } => {
+                let path = self.eval_path(path, output)?;
                 let key_parts: Result<Vec<Value>> = indices
                     .iter()
                     .map(|e| self.eval_expr_with_output(e, output))
                     .collect();
                 let key = self.make_array_key(&key_parts?);
-                Ok(self.get_array_element(array, &key))
+                self.read_array_element(array, &path, &key)
+                    .map_err(|e| at_location(e, location))
             }
 
             Expr::Binary {
@@ -104,19 +129,48 @@ impl<'a> Interpreter<'a> {
                 name,
                 args,
                 location,
-            } => self.call_function(name, args, *location, output),
+            } => self.call_function(name, args, location.clone(), output),
+
+            Expr::IndirectCall {
+                var,
+                args,
+                location,
+            } => {
+                let name = self.get_variable(var).to_string_val();
+                if !self.functions.contains_key(name.as_str()) && !is_builtin_function(&name) {
+                    return Err(Error::runtime_in(
+                        format!(
+                            "function `{}' called indirectly through `{}' is not defined",
+                            name, var
+                        ),
+                        location.clone(),
+                    ));
+                }
+                self.call_function(&name, args, location.clone(), output)
+            }
 
-            Expr::InArray { key, array, .. } => {
+            Expr::InArray {
+                key,
+                array,
+                path,
+                location,
+            } => {
                 let key_parts: Result<Vec<Value>> = key
                     .iter()
                     .map(|e| self.eval_expr_with_output(e, output))
                     .collect();
                 let key_str = self.make_array_key(&key_parts?);
-                Ok(Value::Number(if self.array_key_exists(array, &key_str) {
-                    1.0
-                } else {
-                    0.0
-                }))
+                let path = self.eval_path(path, output)?;
+                Ok(Value::Number(
+                    if self
+                        .array_key_exists(array, &path, &key_str)
+                        .map_err(|e| at_location(e, location))?
+                    {
+                        1.0
+                    } else {
+                        0.0
+                    },
+                ))
             }
 
             Expr::Match {
@@ -127,7 +181,7 @@ impl<'a> Interpreter<'a> {
             } => {
                 let string = self.eval_expr_with_output(expr, output)?.to_string_val();
                 let pattern_str = match pattern.as_ref() {
-                    Expr::Regex(p, _) => p.clone(),
+                    Expr::Regex(p, _) | Expr::TypedRegex(p, _) => p.clone(),
                     other => self.eval_expr_with_output(other, output)?.to_string_val(),
                 };
                 let re = self.get_regex(&pattern_str)?;
@@ -148,7 +202,7 @@ impl<'a> Interpreter<'a> {
                 var,
                 input,
                 location,
-            } => self.eval_getline(var.as_ref(), input.as_ref(), *location),
+            } => self.eval_getline(var.as_ref(), input.as_ref(), location.clone()),
 
             Expr::Group(expr, _) => self.eval_expr_with_output(expr, output),
         }
@@ -206,32 +260,32 @@ impl<'a> Interpreter<'a> {
                 }
             }
             BinaryOp::Pow => Ok(Value::Number(l.to_number().powf(r.to_number()))),
-            BinaryOp::Lt => Ok(Value::Number(if compare_values(&l, &r).is_lt() {
+            BinaryOp::Lt => Ok(Value::Number(if self.compare(&l, &r).is_lt() {
                 1.0
             } else {
                 0.0
             })),
-            BinaryOp::Le => Ok(Value::Number(if compare_values(&l, &r).is_le() {
+            BinaryOp::Le => Ok(Value::Number(if self.compare(&l, &r).is_le() {
                 1.0
             } else {
                 0.0
             })),
-            BinaryOp::Gt => Ok(Value::Number(if compare_values(&l, &r).is_gt() {
+            BinaryOp::Gt => Ok(Value::Number(if self.compare(&l, &r).is_gt() {
                 1.0
             } else {
                 0.0
             })),
-            BinaryOp::Ge => Ok(Value::Number(if compare_values(&l, &r).is_ge() {
+            BinaryOp::Ge => Ok(Value::Number(if self.compare(&l, &r).is_ge() {
                 1.0
             } else {
                 0.0
             })),
-            BinaryOp::Eq => Ok(Value::Number(if compare_values(&l, &r).is_eq() {
+            BinaryOp::Eq => Ok(Value::Number(if self.compare(&l, &r).is_eq() {
                 1.0
             } else {
                 0.0
             })),
-            BinaryOp::Ne => Ok(Value::Number(if compare_values(&l, &r).is_ne() {
+            BinaryOp::Ne => Ok(Value::Number(if self.compare(&l, &r).is_ne() {
                 1.0
             } else {
                 0.0
@@ -294,45 +348,58 @@ impl<'a> Interpreter<'a> {
         input: Option<&GetlineInput>,
         _location: crate::error::SourceLocation,
     ) -> Result<Value> {
-        use std::io::BufRead;
-
         match input {
             None => {
-                // getline with no input source - read from current input
-                // This is handled by the main loop, so we return 0 (EOF) here
-                Ok(Value::Number(0.0))
+                // Plain getline reads the next record of the main input
+                match self.read_main_record() {
+                    Ok(None) => Ok(Value::Number(0.0)), // EOF
+                    Ok(Some(record)) => {
+                        self.nr += 1;
+                        self.fnr += 1;
+                        self.rt = record.terminator;
+                        if let Some(var_name) = var {
+                            let value = self.input_value(record.text);
+                            self.set_variable_value(var_name, value);
+                        } else {
+                            self.set_record(&record.text);
+                        }
+                        Ok(Value::Number(1.0)) // Success
+                    }
+                    Err(_) => Ok(Value::Number(-1.0)), // Error
+                }
             }
             Some(GetlineInput::File(file_expr)) => {
                 let filename = self.eval_expr(file_expr)?.to_string_val();
 
-                // Get or open the file
+                // Get or open the file ("-" and /dev/stdin are standard input)
                 if !self.input_files.contains_key(&filename) {
-                    match std::fs::File::open(&filename) {
-                        Ok(file) => {
-                            self.input_files
-                                .insert(filename.clone(), std::io::BufReader::new(file));
+                    let source = if is_stdin_name(&filename) {
+                        RecordSource::Stdin(std::rc::Rc::clone(&self.stdin))
+                    } else {
+                        let file = match open_fd_name(&filename) {
+                            Some(file) => file,
+                            None => std::fs::File::open(&filename),
+                        };
+                        match file {
+                            Ok(file) => RecordSource::new(Box::new(std::io::BufReader::new(file))
+                                as Box<dyn std::io::BufRead>),
+                            Err(_) => return Ok(Value::Number(-1.0)), // Error
                         }
-                        Err(_) => return Ok(Value::Number(-1.0)), // Error
-                    }
+                    };
+                    self.input_files.insert(filename.clone(), source);
                 }
 
+                let (single_char, ignore_case) = (self.single_char_rs(), self.ignore_case());
                 let reader = self.input_files.get_mut(&filename).unwrap();
-                let mut line = String::new();
-                match reader.read_line(&mut line) {
-                    Ok(0) => Ok(Value::Number(0.0)), // EOF
-                    Ok(_) => {
-                        // Remove trailing newline
-                        if line.ends_with('\n') {
-                            line.pop();
-                            if line.ends_with('\r') {
-                                line.pop();
-                            }
-                        }
-
+                match reader.read_record(&self.rs, single_char, ignore_case) {
+                    Ok(None) => Ok(Value::Number(0.0)), // EOF
+                    Ok(Some(record)) => {
+                        self.rt = record.terminator;
                         if let Some(var_name) = var {
-                            self.set_variable_value(var_name, Value::from_string(line));
+                            let value = self.input_value(record.text);
+                            self.set_variable_value(var_name, value);
                         } else {
-                            self.set_record(&line);
+                            self.set_record(&record.text);
                         }
                         Ok(Value::Number(1.0)) // Success
                     }
@@ -356,7 +423,7 @@ impl<'a> Interpreter<'a> {
                                 cmd.clone(),
                                 super::PipeInput {
                                     child,
-                                    reader: std::io::BufReader::new(stdout),
+                                    reader: RecordReader::new(std::io::BufReader::new(stdout)),
                                 },
                             );
                         }
@@ -364,23 +431,50 @@ impl<'a> Interpreter<'a> {
                     }
                 }
 
+                let (single_char, ignore_case) = (self.single_char_rs(), self.ignore_case());
                 let pipe = self.pipes.get_mut(&cmd).unwrap();
-                let mut line = String::new();
-                match pipe.reader.read_line(&mut line) {
-                    Ok(0) => Ok(Value::Number(0.0)), // EOF
-                    Ok(_) => {
-                        // Remove trailing newline
-                        if line.ends_with('\n') {
-                            line.pop();
-                            if line.ends_with('\r') {
-                                line.pop();
-                            }
+                match pipe.reader.read_record(&self.rs, single_char, ignore_case) {
+                    Ok(None) => Ok(Value::Number(0.0)), // EOF
+                    Ok(Some(record)) => {
+                        self.rt = record.terminator;
+                        if let Some(var_name) = var {
+                            let value = self.input_value(record.text);
+                            self.set_variable_value(var_name, value);
+                        } else {
+                            self.set_record(&record.text);
                         }
+                        Ok(Value::Number(1.0)) // Success
+                    }
+                    Err(_) => Ok(Value::Number(-1.0)), // Error
+                }
+            }
+            Some(GetlineInput::Coprocess(cmd_expr)) => {
+                let cmd = self.eval_expr(cmd_expr)?.to_string_val();
 
+                // Reading from a coprocess that was never written to starts it
+                if !self.coprocesses.contains_key(&cmd) {
+                    match Coprocess::spawn(&cmd) {
+                        Ok(coprocess) => {
+                            self.coprocesses.insert(cmd.clone(), coprocess);
+                        }
+                        Err(_) => return Ok(Value::Number(-1.0)), // Error
+                    }
+                }
+
+                let (single_char, ignore_case) = (self.single_char_rs(), self.ignore_case());
+                let coprocess = self.coprocesses.get_mut(&cmd).unwrap();
+                match coprocess
+                    .reader()?
+                    .read_record(&self.rs, single_char, ignore_case)
+                {
+                    Ok(None) => Ok(Value::Number(0.0)), // EOF
+                    Ok(Some(record)) => {
+                        self.rt = record.terminator;
                         if let Some(var_name) = var {
-                            self.set_variable_value(var_name, Value::from_string(line));
+                            let value = self.input_value(record.text);
+                            self.set_variable_value(var_name, value);
                         } else {
-                            self.set_record(&line);
+                            self.set_record(&record.text);
                         }
                         Ok(Value::Number(1.0)) // Success
                     }
@@ -390,20 +484,50 @@ impl<'a> Interpreter<'a> {
         }
     }
 
+    /// Evaluate the subscripts leading to a subarray, one key per `[...]`
+    pub(crate) fn eval_path<W: Write>(
+        &mut self,
+        path: &[Vec<Expr>],
+        output: &mut W,
+    ) -> Result<Vec<String>> {
+        let mut keys = Vec::with_capacity(path.len());
+        for indices in path {
+            let key_parts: Result<Vec<Value>> = indices
+                .iter()
+                .map(|e| self.eval_expr_with_output(e, output))
+                .collect();
+            keys.push(self.make_array_key(&key_parts?));
+        }
+        Ok(keys)
+    }
+
     pub fn assign_to_lvalue(&mut self, target: &Expr, value: Value) -> Result<()> {
         match target {
-            Expr::Var(name, _) => {
+            Expr::Var(name, location) => {
+                if !self.variables.contains_key(name) && self.is_array(name) {
+                    return Err(Error::runtime_in(
+                        array_as_scalar(name, &[]),
+                        location.clone(),
+                    ));
+                }
                 self.set_variable_value(name, value);
             }
             Expr::Field(expr, _) => {
                 let index = self.eval_expr(expr)?.to_number() as usize;
                 self.set_field(index, value.to_string_val());
             }
-            Expr::ArrayAccess { array, indices, .. } => {
+            Expr::ArrayAccess {
+                array,
+                path,
+                indices,
+                location,
+            } => {
+                let path = self.eval_path(path, &mut std::io::sink())?;
                 let key_parts: Result<Vec<Value>> =
                     indices.iter().map(|e| self.eval_expr(e)).collect();
                 let key = self.make_array_key(&key_parts?);
-                self.set_array_element(array, &key, value);
+                self.set_array_element(array, &path, &key, value)
+                    .map_err(|e| at_location(e, location))?;
             }
             _ => {
                 // Invalid lvalue, but we'll just ignore for now
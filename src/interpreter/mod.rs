@@ -1,45 +1,186 @@
+mod array;
 mod builtins;
+mod coprocess;
+mod ere;
 mod expr;
+mod record;
+mod sort;
 pub mod stmt;
 
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Stderr, Write};
 use std::process::{Child, ChildStdin, ChildStdout};
+use std::rc::Rc;
 
 use crate::ast::*;
-use crate::error::{Error, Result};
-use crate::value::Value;
-
+use crate::dialect::{Dialect, GAWK_VARIABLES};
+use crate::error::{Error, Result, SourceLocation};
+use crate::lexer::unescape;
+use crate::value::{Value, compare_values_folding};
+
+use array::{ArrayRef, AwkArray, Element, element_name};
+use coprocess::Coprocess;
+use record::{Record, RecordReader, RecordSource, SharedStdin};
 use regex::Regex;
 
+/// Where the main input records come from
+enum MainInput<'a> {
+    /// Readers handed to `run`, consumed in order
+    Readers(std::vec::IntoIter<Box<dyn BufRead + 'a>>),
+    /// Operands in ARGV, opened one at a time as they are reached
+    Argv {
+        /// Index of the next ARGV element to examine
+        next: usize,
+        /// Whether a file operand was found (stdin is read otherwise)
+        used_file: bool,
+    },
+}
+
 /// Input source for getline from pipe
 pub struct PipeInput {
-    #[allow(dead_code)]
     child: Child,
-    reader: BufReader<ChildStdout>,
+    reader: RecordReader<BufReader<ChildStdout>>,
+}
+
+impl PipeInput {
+    /// Close the pipe and wait for the command, returning close()'s result
+    fn close(self) -> f64 {
+        let PipeInput { mut child, reader } = self;
+        drop(reader);
+        wait_status(&mut child)
+    }
 }
 
 /// Output destination for print/printf redirection
 pub enum OutputFile {
     File(File),
-    Pipe(ChildStdin),
+    Pipe {
+        stdin: ChildStdin,
+        child: Child,
+    },
+    /// `/dev/stderr` or `/dev/fd/2`
+    Stderr(Stderr),
+}
+
+impl OutputFile {
+    /// Flush and close the output, returning close()'s result
+    ///
+    /// For a pipe this waits for the command to finish.
+    fn close(self) -> f64 {
+        match self {
+            OutputFile::File(mut f) => match f.flush() {
+                Ok(()) => 0.0,
+                Err(_) => -1.0,
+            },
+            OutputFile::Pipe { stdin, mut child } => {
+                drop(stdin);
+                wait_status(&mut child)
+            }
+            OutputFile::Stderr(mut e) => match e.flush() {
+                Ok(()) => 0.0,
+                Err(_) => -1.0,
+            },
+        }
+    }
 }
 
 impl Write for OutputFile {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             OutputFile::File(f) => f.write(buf),
-            OutputFile::Pipe(p) => p.write(buf),
+            OutputFile::Pipe { stdin, .. } => stdin.write(buf),
+            OutputFile::Stderr(e) => e.write(buf),
         }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             OutputFile::File(f) => f.flush(),
-            OutputFile::Pipe(p) => p.flush(),
+            OutputFile::Pipe { stdin, .. } => stdin.flush(),
+            OutputFile::Stderr(e) => e.flush(),
+        }
+    }
+}
+
+/// Whether a file name refers to the interpreter's own standard output
+pub(crate) fn is_stdout_name(name: &str) -> bool {
+    matches!(name, "-" | "/dev/stdout" | "/dev/fd/1")
+}
+
+/// Whether a file name refers to the process's standard error
+pub(crate) fn is_stderr_name(name: &str) -> bool {
+    matches!(name, "/dev/stderr" | "/dev/fd/2")
+}
+
+/// Whether a file name given to `getline <` refers to standard input
+pub(crate) fn is_stdin_name(name: &str) -> bool {
+    matches!(name, "-" | "/dev/stdin" | "/dev/fd/0")
+}
+
+/// Attach a location to a runtime error raised without one
+///
+/// Array lookups and builtins report errors without knowing where they were
+/// called from; the expression or statement that called them adds it.
+pub(crate) fn at_location(error: Error, location: &SourceLocation) -> Error {
+    match error {
+        Error::Runtime { message } => Error::runtime_in(message, location.clone()),
+        other => other,
+    }
+}
+
+/// Duplicate the inherited descriptor a `/dev/fd/N` name refers to
+///
+/// Returns `None` for other names. The duplicate shares the descriptor's file
+/// offset, and unlike reopening the path it works wherever the descriptor
+/// came from.
+#[cfg(unix)]
+pub(crate) fn open_fd_name(name: &str) -> Option<std::io::Result<File>> {
+    use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+    let fd: RawFd = name.strip_prefix("/dev/fd/")?.parse().ok()?;
+    if fd < 0 {
+        return None;
+    }
+    // SAFETY: fcntl only inspects the descriptor table; a number that is not
+    // an open descriptor makes it fail with EBADF
+    let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if dup < 0 {
+        return Some(Err(std::io::Error::last_os_error()));
+    }
+    // SAFETY: `dup` is a new descriptor that nothing else owns
+    Some(Ok(File::from(unsafe { OwnedFd::from_raw_fd(dup) })))
+}
+
+/// `/dev/fd/N` names are opened as ordinary paths where there are no
+/// inherited descriptors to duplicate
+#[cfg(not(unix))]
+pub(crate) fn open_fd_name(_name: &str) -> Option<std::io::Result<File>> {
+    None
+}
+
+/// Wait for a child process and convert its status the way gawk's close() does
+///
+/// A normal exit gives the exit code; death by a signal gives 256 + signal
+/// (512 + signal if it dumped core). Returns -1 if waiting fails.
+pub(crate) fn wait_status(child: &mut Child) -> f64 {
+    let Ok(status) = child.wait() else {
+        return -1.0;
+    };
+    if let Some(code) = status.code() {
+        return code as f64;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            let base = if status.core_dumped() { 512 } else { 256 };
+            return (base + signal) as f64;
         }
     }
+    -1.0
 }
 
 /// The AWK interpreter runtime
@@ -51,7 +192,7 @@ pub struct Interpreter<'a> {
     pub(crate) variables: HashMap<String, Value>,
 
     /// Associative arrays
-    pub(crate) arrays: HashMap<String, HashMap<String, Value>>,
+    pub(crate) arrays: HashMap<String, AwkArray>,
 
     /// User-defined functions
     pub(crate) functions: HashMap<String, &'a FunctionDef>,
@@ -79,6 +220,10 @@ pub struct Interpreter<'a> {
     /// Mode flags
     pub(crate) posix_mode: bool,
     pub(crate) traditional_mode: bool,
+    /// IGNORECASE is non-zero - gawk extension
+    pub(crate) ignorecase: bool,
+    /// Hex and octal input data are numbers (--non-decimal-data) - gawk extension
+    pub(crate) non_decimal_data: bool,
 
     /// Current record ($0)
     pub(crate) record: String,
@@ -92,6 +237,8 @@ pub struct Interpreter<'a> {
     pub(crate) fnr: usize,
     /// Current filename (FILENAME)
     pub(crate) filename: String,
+    /// Text that terminated the current record (RT) - gawk extension
+    pub(crate) rt: String,
 
     /// RSTART and RLENGTH from match()
     pub(crate) rstart: usize,
@@ -100,20 +247,33 @@ pub struct Interpreter<'a> {
     /// Control flow flags
     should_exit: bool,
     exit_code: i32,
+    /// Problems that did not stop the run, such as unreadable input files
+    warnings: Vec<String>,
     should_next: bool,
     should_nextfile: bool,
 
+    /// Source of the main input and the reader for the current file
+    main_input: MainInput<'a>,
+    current_input: Option<RecordSource<Box<dyn BufRead + 'a>>>,
+
     /// Open files for output redirection
     pub(crate) output_files: HashMap<String, OutputFile>,
 
     /// Open files for input (getline)
-    pub(crate) input_files: HashMap<String, BufReader<File>>,
+    pub(crate) input_files: HashMap<String, RecordSource<Box<dyn BufRead>>>,
+
+    /// Standard input, read by the main input and `getline < "-"` alike
+    pub(crate) stdin: SharedStdin,
 
     /// Open pipes for input (getline from command)
     pub(crate) pipes: HashMap<String, PipeInput>,
 
-    /// Compiled regex cache
+    /// Two-way pipes to coprocesses (`|&`)
+    pub(crate) coprocesses: HashMap<String, Coprocess>,
+
+    /// Compiled regex caches, for case-sensitive and IGNORECASE matching
     pub(crate) regex_cache: HashMap<String, Regex>,
+    pub(crate) regex_cache_icase: HashMap<String, Regex>,
 
     /// Range pattern state (for /start/,/end/ patterns)
     range_states: HashMap<usize, bool>,
@@ -122,16 +282,15 @@ pub struct Interpreter<'a> {
     pub(crate) rand_seed: u64,
     pub(crate) rand_state: u64,
 
-    /// Command line arguments (ARGC, ARGV)
-    pub(crate) argc: usize,
-    pub(crate) argv: Vec<String>,
-
     /// Environment variables (ENVIRON)
     pub(crate) environ: HashMap<String, String>,
 
-    /// Array aliases for pass-by-reference in functions
-    /// Maps parameter name -> actual array name
-    pub(crate) array_aliases: HashMap<String, String>,
+    /// Array parameters of the running function, bound to the array (or
+    /// subarray) each one refers to
+    pub(crate) array_aliases: HashMap<String, ArrayRef>,
+
+    /// Depth of user function calls, used to name local arrays
+    pub(crate) call_depth: usize,
 }
 
 impl<'a> Interpreter<'a> {
@@ -167,36 +326,54 @@ impl<'a> Interpreter<'a> {
             fieldwidths: String::new(),
             posix_mode: false,
             traditional_mode: false,
+            ignorecase: false,
+            non_decimal_data: false,
             record: String::new(),
             fields: Vec::new(),
             nf: 0,
             nr: 0,
             fnr: 0,
             filename: String::new(),
+            rt: String::new(),
             rstart: 0,
             rlength: -1,
             should_exit: false,
             exit_code: 0,
+            warnings: Vec::new(),
             should_next: false,
             should_nextfile: false,
+            main_input: MainInput::Readers(Vec::new().into_iter()),
+            current_input: None,
             output_files: HashMap::new(),
             input_files: HashMap::new(),
+            stdin: Rc::new(RefCell::new(RecordReader::new(BufReader::new(
+                std::io::stdin(),
+            )))),
             pipes: HashMap::new(),
+            coprocesses: HashMap::new(),
             regex_cache: HashMap::new(),
+            regex_cache_icase: HashMap::new(),
             range_states: HashMap::new(),
             rand_seed,
             rand_state: rand_seed,
-            argc: 0,
-            argv: Vec::new(),
             environ,
             array_aliases: HashMap::new(),
+            call_depth: 0,
         }
     }
 
     /// Set command line arguments (ARGC and ARGV)
+    ///
+    /// ARGV is an ordinary array, so the program may add, change or delete
+    /// operands before they are reached.
     pub fn set_args(&mut self, args: Vec<String>) {
-        self.argc = args.len();
-        self.argv = args;
+        self.variables
+            .insert("ARGC".to_string(), Value::Number(args.len() as f64));
+        let argv = self.arrays.entry("ARGV".to_string()).or_default();
+        argv.clear();
+        for (i, arg) in args.into_iter().enumerate() {
+            argv.insert(i.to_string(), Value::from_input(arg));
+        }
     }
 
     /// Set the field separator
@@ -215,6 +392,11 @@ impl<'a> Interpreter<'a> {
         }
     }
 
+    /// Treat hex (`0x1F`) and octal (`017`) input data as numbers
+    pub fn set_non_decimal_data(&mut self, enabled: bool) {
+        self.non_decimal_data = enabled;
+    }
+
     /// Set traditional AWK mode (no gawk extensions)
     pub fn set_traditional_mode(&mut self, enabled: bool) {
         self.traditional_mode = enabled;
@@ -223,10 +405,19 @@ impl<'a> Interpreter<'a> {
         }
     }
 
-    /// Set a variable before execution
-    pub fn set_variable(&mut self, name: &str, value: &str) {
-        self.variables
-            .insert(name.to_string(), Value::from_string(value.to_string()));
+    /// Set a variable from a command line assignment (`-v` or an operand)
+    ///
+    /// Escape sequences in `value` are processed as in a string literal, so a
+    /// `\x` escape is an error outside gawk mode.
+    pub fn set_variable(&mut self, name: &str, value: &str) -> Result<()> {
+        let value = unescape(value, self.dialect()).map_err(Error::runtime)?;
+        self.set_variable_value(name, Value::from_input(value));
+        Ok(())
+    }
+
+    /// Warnings from the run so far, for the caller to report
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
     /// Set the current filename (FILENAME)
@@ -235,182 +426,274 @@ impl<'a> Interpreter<'a> {
     }
 
     /// Run the AWK program with given input
-    pub fn run<R: BufRead, W: Write>(&mut self, inputs: Vec<R>, output: &mut W) -> Result<i32> {
+    pub fn run<R: BufRead + 'a, W: Write>(
+        &mut self,
+        inputs: Vec<R>,
+        output: &mut W,
+    ) -> Result<i32> {
+        let readers: Vec<Box<dyn BufRead + 'a>> = inputs
+            .into_iter()
+            .map(|r| Box::new(r) as Box<dyn BufRead + 'a>)
+            .collect();
+        self.main_input = MainInput::Readers(readers.into_iter());
+        self.execute(output)
+    }
+
+    /// Run the AWK program, reading the files named in ARGV (stdin if none)
+    pub fn run_argv<W: Write>(&mut self, output: &mut W) -> Result<i32> {
+        self.main_input = MainInput::Argv {
+            next: 1,
+            used_file: false,
+        };
+        self.execute(output)
+    }
+
+    fn execute<W: Write>(&mut self, output: &mut W) -> Result<i32> {
         // Execute BEGIN rules
+        self.run_special_rules(|p| matches!(p, Pattern::Begin), output)?;
+
+        // Programs consisting only of BEGIN rules do not read input
+        let needs_input = self
+            .program
+            .rules
+            .iter()
+            .any(|rule| !matches!(&rule.pattern, Some(Pattern::Begin)));
+
+        if needs_input && !self.should_exit {
+            self.process_main_input(output)?;
+        }
+
+        // END rules still run after exit in BEGIN or a main rule
+        self.should_exit = false;
+        self.run_special_rules(|p| matches!(p, Pattern::End), output)?;
+
+        output.flush().map_err(Error::Io)?;
+        self.close_all_streams();
+
+        Ok(self.exit_code)
+    }
+
+    /// Flush and close every redirection, waiting for all child processes
+    fn close_all_streams(&mut self) {
+        for (_, file) in self.output_files.drain() {
+            file.close();
+        }
+        self.input_files.clear();
+        for (_, pipe) in self.pipes.drain() {
+            pipe.close();
+        }
+        for (_, coprocess) in self.coprocesses.drain() {
+            coprocess.close();
+        }
+    }
+
+    /// Execute the actions of BEGIN/END/BEGINFILE/ENDFILE rules, stopping at exit
+    fn run_special_rules<W: Write>(
+        &mut self,
+        is_kind: impl Fn(&Pattern) -> bool,
+        output: &mut W,
+    ) -> Result<()> {
         for rule in &self.program.rules {
-            if matches!(&rule.pattern, Some(Pattern::Begin)) {
+            if let Some(pattern) = &rule.pattern
+                && is_kind(pattern)
+            {
                 if let Some(action) = &rule.action {
                     self.execute_block(action, output)?;
                 }
                 if self.should_exit {
-                    return Ok(self.exit_code);
+                    break;
                 }
             }
         }
+        Ok(())
+    }
 
-        // Process input files
-        for input in inputs {
-            self.fnr = 0;
+    fn process_main_input<W: Write>(&mut self, output: &mut W) -> Result<()> {
+        loop {
+            if self.current_input.is_none() {
+                if !self.open_next_input()? {
+                    return Ok(());
+                }
 
-            // Execute BEGINFILE rules (gawk extension)
-            for rule in &self.program.rules {
-                if matches!(&rule.pattern, Some(Pattern::BeginFile)) {
-                    if let Some(action) = &rule.action {
-                        self.execute_block(action, output)?;
-                    }
-                    if self.should_exit {
-                        return Ok(self.exit_code);
-                    }
+                // Execute BEGINFILE rules (gawk extension)
+                self.run_special_rules(|p| matches!(p, Pattern::BeginFile), output)?;
+                if self.should_exit {
+                    return Ok(());
                 }
             }
 
-            self.process_input(input, output)?;
+            let (single_char, ignore_case) = (self.single_char_rs(), self.ignore_case());
+            let record = match self.current_input.as_mut() {
+                Some(reader) => reader.read_record(&self.rs, single_char, ignore_case)?,
+                None => continue,
+            };
 
-            // Execute ENDFILE rules (gawk extension)
-            for rule in &self.program.rules {
-                if matches!(&rule.pattern, Some(Pattern::EndFile)) {
-                    if let Some(action) = &rule.action {
-                        self.execute_block(action, output)?;
-                    }
-                    if self.should_exit {
-                        return Ok(self.exit_code);
-                    }
+            match record {
+                Some(record) => {
+                    self.nr += 1;
+                    self.fnr += 1;
+                    self.rt = record.terminator;
+                    self.set_record(&record.text);
+
+                    self.process_current_record(output)?;
                 }
+                None => self.should_nextfile = true,
+            }
+
+            if self.should_nextfile {
+                self.should_nextfile = false;
+                self.current_input = None;
+
+                // Execute ENDFILE rules (gawk extension)
+                self.run_special_rules(|p| matches!(p, Pattern::EndFile), output)?;
             }
 
             if self.should_exit {
-                return Ok(self.exit_code);
+                return Ok(());
             }
         }
+    }
 
-        // Execute END rules
-        for rule in &self.program.rules {
-            if matches!(&rule.pattern, Some(Pattern::End))
-                && let Some(action) = &rule.action
+    /// Read the next record of the main input for a plain `getline`
+    ///
+    /// At the end of a file this moves on to the next one, without running
+    /// ENDFILE or BEGINFILE rules.
+    pub(crate) fn read_main_record(&mut self) -> Result<Option<Record>> {
+        loop {
+            if self.current_input.is_none() && !self.open_next_input()? {
+                return Ok(None);
+            }
+
+            let (single_char, ignore_case) = (self.single_char_rs(), self.ignore_case());
+            if let Some(reader) = self.current_input.as_mut()
+                && let Some(record) = reader.read_record(&self.rs, single_char, ignore_case)?
             {
-                self.execute_block(action, output)?;
+                return Ok(Some(record));
             }
+            self.current_input = None;
         }
-
-        Ok(self.exit_code)
     }
 
-    fn process_input<R: BufRead, W: Write>(&mut self, mut input: R, output: &mut W) -> Result<()> {
-        // Check for paragraph mode (RS = "")
-        if self.rs.is_empty() {
-            return self.process_input_paragraph_mode(input, output);
-        }
-
-        let mut line = String::new();
+    /// Open the next main input file, resetting FNR
+    ///
+    /// Returns false once every input has been consumed.
+    fn open_next_input(&mut self) -> Result<bool> {
+        let reader = if let MainInput::Readers(readers) = &mut self.main_input {
+            readers.next().map(RecordSource::new)
+        } else {
+            self.next_argv_input()?
+        };
 
-        loop {
-            line.clear();
-            let bytes_read = input.read_line(&mut line).map_err(Error::Io)?;
-            if bytes_read == 0 {
-                break; // EOF
-            }
+        let Some(reader) = reader else {
+            return Ok(false);
+        };
+        self.current_input = Some(reader);
+        self.fnr = 0;
+        Ok(true)
+    }
+
+    /// Walk ARGV up to the next file operand
+    ///
+    /// ARGV and ARGC are read afresh each time, so changes made by the program
+    /// are honoured. Empty elements are skipped and `name=value` operands are
+    /// assigned when they are reached. Standard input is read if no file
+    /// operand is found.
+    fn next_argv_input(&mut self) -> Result<Option<RecordSource<Box<dyn BufRead + 'a>>>> {
+        let MainInput::Argv {
+            mut next,
+            mut used_file,
+        } = self.main_input
+        else {
+            return Ok(None);
+        };
 
-            // Remove record separator
-            if line.ends_with('\n') {
-                line.pop();
-                if line.ends_with('\r') {
-                    line.pop();
+        let reader = loop {
+            let argc = self.get_variable("ARGC").to_number();
+            if next as f64 >= argc {
+                if used_file {
+                    break None;
                 }
+                used_file = true;
+                self.filename.clear();
+                break Some(self.open_operand("-")?);
             }
 
-            self.nr += 1;
-            self.fnr += 1;
-            self.set_record(&line);
-
-            self.process_current_record(output)?;
+            let operand = self.get_array_element("ARGV", &[], &next.to_string())?;
+            let operand = operand.to_string_val();
+            next += 1;
 
-            if self.should_nextfile {
-                self.should_nextfile = false;
-                break;
+            if operand.is_empty() {
+                continue;
+            }
+            if let Some((name, value)) = split_assignment(&operand) {
+                self.set_variable(name, value)?;
+                continue;
             }
 
-            if self.should_exit {
-                break;
+            // An unreadable file is skipped with a warning, and the run
+            // ends with status 2 unless the program exits with another
+            used_file = true;
+            match self.open_operand(&operand) {
+                Ok(reader) => {
+                    self.filename = operand;
+                    break Some(reader);
+                }
+                Err(e) => {
+                    self.warnings
+                        .push(format!("cannot open file `{}' for reading: {}", operand, e));
+                    self.exit_code = 2;
+                }
             }
-        }
+        };
 
-        Ok(())
+        self.main_input = MainInput::Argv { next, used_file };
+        Ok(reader)
     }
 
-    /// Process input in paragraph mode (RS = "")
-    /// Blank lines separate records; multiple blank lines count as one separator
-    fn process_input_paragraph_mode<R: BufRead, W: Write>(
-        &mut self,
-        mut input: R,
-        output: &mut W,
-    ) -> Result<()> {
-        let mut line = String::new();
-        let mut record = String::new();
-        let mut in_record = false;
-
-        loop {
-            line.clear();
-            let bytes_read = input.read_line(&mut line).map_err(Error::Io)?;
-
-            // Check if line is blank (empty or only whitespace)
-            let is_blank = line.trim().is_empty();
-
-            if bytes_read == 0 {
-                // EOF - process any remaining record
-                if !record.is_empty() {
-                    // Remove trailing newline
-                    while record.ends_with('\n') || record.ends_with('\r') {
-                        record.pop();
-                    }
-                    self.nr += 1;
-                    self.fnr += 1;
-                    self.set_record(&record);
-                    self.process_current_record(output)?;
-                }
-                break;
-            }
+    /// Open an input file operand, where "-" and `/dev/stdin` mean standard
+    /// input
+    fn open_operand(&self, operand: &str) -> std::io::Result<RecordSource<Box<dyn BufRead + 'a>>> {
+        if is_stdin_name(operand) {
+            return Ok(RecordSource::Stdin(Rc::clone(&self.stdin)));
+        }
+        let file = match open_fd_name(operand) {
+            Some(file) => file?,
+            None => File::open(operand)?,
+        };
+        Ok(RecordSource::new(Box::new(BufReader::new(file))))
+    }
 
-            if is_blank {
-                // Blank line - end of record if we're in one
-                if in_record && !record.is_empty() {
-                    // Remove trailing newline
-                    while record.ends_with('\n') || record.ends_with('\r') {
-                        record.pop();
-                    }
-                    self.nr += 1;
-                    self.fnr += 1;
-                    self.set_record(&record);
-                    self.process_current_record(output)?;
+    /// Whether only the first character of RS is significant (no regex RS)
+    pub(crate) fn single_char_rs(&self) -> bool {
+        self.posix_mode || self.traditional_mode
+    }
 
-                    record.clear();
-                    in_record = false;
+    /// A value read from input: a record, field, `getline` result or `split`
+    /// element
+    pub(crate) fn input_value(&self, s: String) -> Value {
+        let non_decimal = self.non_decimal_data && self.dialect().gawk_extensions();
+        Value::from_input_data(s, non_decimal)
+    }
 
-                    if self.should_nextfile || self.should_exit {
-                        break;
-                    }
-                }
-            } else {
-                // Non-blank line - add to record
-                if in_record {
-                    record.push('\n');
-                }
-                // Remove trailing newline from line before adding
-                if line.ends_with('\n') {
-                    line.pop();
-                    if line.ends_with('\r') {
-                        line.pop();
-                    }
-                }
-                record.push_str(&line);
-                in_record = true;
-            }
+    /// The dialect selected by the mode flags
+    pub(crate) fn dialect(&self) -> Dialect {
+        if self.posix_mode {
+            Dialect::Posix
+        } else if self.traditional_mode {
+            Dialect::Traditional
+        } else {
+            Dialect::Gawk
         }
+    }
 
-        if self.should_nextfile {
-            self.should_nextfile = false;
-        }
+    /// Whether matching ignores case (IGNORECASE, not in POSIX or traditional mode)
+    pub(crate) fn ignore_case(&self) -> bool {
+        self.ignorecase && self.dialect().gawk_extensions()
+    }
 
-        Ok(())
+    /// Compare two values for a relational operator, honouring IGNORECASE
+    pub(crate) fn compare(&self, left: &Value, right: &Value) -> Ordering {
+        compare_values_folding(left, right, self.ignore_case())
     }
 
     /// Process the current record through all matching rules
@@ -484,7 +767,9 @@ impl<'a> Interpreter<'a> {
             // Use byte-based iteration for ASCII optimization
             self.fields
                 .extend(self.record.split_whitespace().map(String::from));
-        } else if self.fs.len() == 1 {
+        } else if self.fs.len() == 1
+            && !(self.ignore_case() && self.fs.as_bytes()[0].is_ascii_alphabetic())
+        {
             // Single character separator - most common case, optimize for it
             let sep = self.fs.as_bytes()[0];
             let bytes = self.record.as_bytes();
@@ -499,14 +784,12 @@ impl<'a> Interpreter<'a> {
             // Don't forget the last field
             self.fields.push(self.record[start..].to_string());
         } else {
-            // Regex separator - cache the compiled regex
+            // Regex separator - compiled regexes are cached by get_regex
             let fs = self.fs.clone();
             let record = self.record.clone();
-            if let Some(regex) = self.regex_cache.get(&fs) {
-                self.fields.extend(regex.split(&record).map(String::from));
-            } else if let Ok(regex) = Regex::new(&fs) {
-                self.fields.extend(regex.split(&record).map(String::from));
-                self.regex_cache.insert(fs, regex);
+            if let Ok(regex) = self.get_regex(&fs) {
+                let fields: Vec<String> = regex.split(&record).map(String::from).collect();
+                self.fields.extend(fields);
             } else {
                 // If regex fails, treat as literal string
                 self.fields.extend(record.split(&fs).map(String::from));
@@ -521,15 +804,12 @@ impl<'a> Interpreter<'a> {
         let fpat = self.fpat.clone();
         let record = self.record.clone();
 
-        if let Some(regex) = self.regex_cache.get(&fpat) {
-            for mat in regex.find_iter(&record) {
-                self.fields.push(mat.as_str().to_string());
-            }
-        } else if let Ok(regex) = Regex::new(&fpat) {
-            for mat in regex.find_iter(&record) {
-                self.fields.push(mat.as_str().to_string());
-            }
-            self.regex_cache.insert(fpat, regex);
+        if let Ok(regex) = self.get_regex(&fpat) {
+            let fields: Vec<String> = regex
+                .find_iter(&record)
+                .map(|mat| mat.as_str().to_string())
+                .collect();
+            self.fields.extend(fields);
         }
 
         self.nf = self.fields.len();
@@ -644,15 +924,28 @@ impl<'a> Interpreter<'a> {
         }
     }
 
+    /// Compile `pattern`, honouring IGNORECASE, and cache the result
     pub(crate) fn get_regex(&mut self, pattern: &str) -> Result<&Regex> {
-        if !self.regex_cache.contains_key(pattern) {
-            let regex = Regex::new(pattern).map_err(Error::Regex)?;
-            self.regex_cache.insert(pattern.to_string(), regex);
+        let ignore_case = self.ignore_case();
+        let dialect = self.dialect();
+        let cache = if ignore_case {
+            &mut self.regex_cache_icase
+        } else {
+            &mut self.regex_cache
+        };
+        if !cache.contains_key(pattern) {
+            let regex = ere::compile(pattern, dialect, ignore_case)?;
+            cache.insert(pattern.to_string(), regex);
         }
-        Ok(self.regex_cache.get(pattern).unwrap())
+        Ok(cache.get(pattern).unwrap())
     }
 
     pub(crate) fn get_variable(&self, name: &str) -> Value {
+        // gawk's special variables are ordinary ones in the other dialects
+        if !self.dialect().gawk_extensions() && GAWK_VARIABLES.contains(&name) {
+            return self.variables.get(name).cloned().unwrap_or_default();
+        }
+
         // Check special variables first
         match name {
             "NF" => Value::Number(self.nf as f64),
@@ -668,10 +961,10 @@ impl<'a> Interpreter<'a> {
             "FILENAME" => Value::from_string(self.filename.clone()),
             "RSTART" => Value::Number(self.rstart as f64),
             "RLENGTH" => Value::Number(self.rlength as f64),
-            "ARGC" => Value::Number(self.argc as f64),
             // gawk extensions
             "FPAT" => Value::from_string(self.fpat.clone()),
             "FIELDWIDTHS" => Value::from_string(self.fieldwidths.clone()),
+            "RT" => Value::from_string(self.rt.clone()),
             _ => self
                 .variables
                 .get(name)
@@ -680,16 +973,11 @@ impl<'a> Interpreter<'a> {
         }
     }
 
-    /// Get an element from ARGV, ENVIRON, or PROCINFO arrays
+    /// Get an element from the ENVIRON or PROCINFO arrays
     pub(crate) fn get_special_array(&self, array: &str, key: &str) -> Option<Value> {
         match array {
-            "ARGV" => key
-                .parse::<usize>()
-                .ok()
-                .and_then(|i| self.argv.get(i))
-                .map(|s| Value::from_string(s.clone())),
-            "ENVIRON" => self.environ.get(key).map(|s| Value::from_string(s.clone())),
-            "PROCINFO" => {
+            "ENVIRON" => self.environ.get(key).map(|s| Value::from_input(s.clone())),
+            "PROCINFO" if self.dialect().gawk_extensions() => {
                 // gawk PROCINFO array - system information
                 match key {
                     "version" => Some(Value::from_string(env!("CARGO_PKG_VERSION").to_string())),
@@ -711,7 +999,8 @@ impl<'a> Interpreter<'a> {
                     "euid" => Some(Value::Number(0.0)), // Platform specific
                     "egid" => Some(Value::Number(0.0)), // Platform specific
                     "pgrpid" => Some(Value::Number(0.0)), // Platform specific
-                    _ => Some(Value::Uninitialized),
+                    // Other elements, such as "sorted_in", are set by the program
+                    _ => None,
                 }
             }
             _ => None,
@@ -719,6 +1008,11 @@ impl<'a> Interpreter<'a> {
     }
 
     pub(crate) fn set_variable_value(&mut self, name: &str, value: Value) {
+        if !self.dialect().gawk_extensions() && GAWK_VARIABLES.contains(&name) {
+            self.variables.insert(name.to_string(), value);
+            return;
+        }
+
         // Handle special variables
         match name {
             "NF" => {
@@ -756,67 +1050,185 @@ impl<'a> Interpreter<'a> {
                 // FIELDWIDTHS takes precedence over FS
                 self.fpat.clear();
             }
+            "RT" => self.rt = value.to_string_val(),
+            "IGNORECASE" => {
+                self.ignorecase = value.is_truthy();
+                self.variables.insert(name.to_string(), value);
+            }
             _ => {
                 self.variables.insert(name.to_string(), value);
             }
         }
     }
 
-    /// Resolve array name through aliases (for pass-by-reference in functions)
-    fn resolve_array_name<'b>(&'b self, array: &'b str) -> &'b str {
-        self.array_aliases
-            .get(array)
-            .map(|s| s.as_str())
-            .unwrap_or(array)
+    /// The array a name and subscripts refer to, following parameter bindings
+    pub(crate) fn array_ref(&self, array: &str, path: Vec<String>) -> ArrayRef {
+        match self.array_aliases.get(array) {
+            Some(alias) => ArrayRef {
+                name: alias.name.clone(),
+                path: alias.path.iter().cloned().chain(path).collect(),
+            },
+            None => ArrayRef {
+                name: array.to_string(),
+                path,
+            },
+        }
     }
 
-    pub(crate) fn get_array_element(&self, array: &str, key: &str) -> Value {
-        let array = self.resolve_array_name(array);
+    /// Find an array or subarray; `None` if it does not exist yet
+    pub(crate) fn lookup_array(&self, array: &str, path: &[String]) -> Result<Option<&AwkArray>> {
+        let (name, prefix) = match self.array_aliases.get(array) {
+            Some(alias) => (alias.name.as_str(), alias.path.as_slice()),
+            None => (array, &[][..]),
+        };
+        let Some(mut current) = self.arrays.get(name) else {
+            if self.variables.contains_key(array) {
+                return Err(Error::runtime(scalar_as_array(array, &[])));
+            }
+            return Ok(None);
+        };
+        for (i, key) in prefix.iter().chain(path).enumerate() {
+            match current.get(key) {
+                Some(Element::Array(sub)) => current = sub,
+                Some(Element::Scalar(_)) => {
+                    let depth = (i + 1).saturating_sub(prefix.len());
+                    return Err(Error::runtime(scalar_as_array(array, &path[..depth])));
+                }
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
 
+    /// Find an array or subarray, creating it and any enclosing subarrays
+    pub(crate) fn lookup_array_mut(
+        &mut self,
+        array: &str,
+        path: &[String],
+    ) -> Result<&mut AwkArray> {
+        let (name, prefix) = match self.array_aliases.get(array) {
+            Some(alias) => (alias.name.clone(), alias.path.clone()),
+            None => (array.to_string(), Vec::new()),
+        };
+        if !self.arrays.contains_key(&name) && self.variables.contains_key(array) {
+            return Err(Error::runtime(scalar_as_array(array, &[])));
+        }
+        let mut current = self.arrays.entry(name).or_default();
+        for (i, key) in prefix.iter().chain(path).enumerate() {
+            current = match current.subarray_mut(key) {
+                Some(sub) => sub,
+                None => {
+                    let depth = (i + 1).saturating_sub(prefix.len());
+                    return Err(Error::runtime(scalar_as_array(array, &path[..depth])));
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    /// Whether a name refers to an array (never an error, unlike lookups)
+    pub(crate) fn is_array(&self, name: &str) -> bool {
+        matches!(self.lookup_array(name, &[]), Ok(Some(_)))
+    }
+
+    /// Whether a name has not been used as a scalar or an array yet
+    ///
+    /// An untyped variable passed to a function becomes whatever the
+    /// function uses it as.
+    pub(crate) fn is_untyped(&self, name: &str) -> bool {
+        !self.variables.contains_key(name)
+            && matches!(self.get_variable(name), Value::Uninitialized)
+            && !self.is_array(name)
+    }
+
+    pub(crate) fn get_array_element(
+        &self,
+        array: &str,
+        path: &[String],
+        key: &str,
+    ) -> Result<Value> {
         // Check for special arrays first
-        if let Some(val) = self.get_special_array(array, key) {
-            return val;
+        if path.is_empty()
+            && !self.array_aliases.contains_key(array)
+            && let Some(val) = self.get_special_array(array, key)
+        {
+            return Ok(val);
         }
 
-        self.arrays
-            .get(array)
-            .and_then(|arr| arr.get(key))
-            .cloned()
-            .unwrap_or(Value::Uninitialized)
+        match self.lookup_array(array, path)?.and_then(|arr| arr.get(key)) {
+            Some(Element::Scalar(value)) => Ok(value.clone()),
+            Some(Element::Array(_)) => Err(Error::runtime(element_as_scalar(array, path, key))),
+            None => Ok(Value::Uninitialized),
+        }
     }
 
-    pub(crate) fn set_array_element(&mut self, array: &str, key: &str, value: Value) {
-        let array = self.resolve_array_name(array).to_string();
-        self.arrays
-            .entry(array)
-            .or_default()
-            .insert(key.to_string(), value);
+    /// Read an element as an rvalue, creating it (untyped) if it is missing
+    ///
+    /// As in other awks, a reference like `x = a[k]` brings `a[k]` into
+    /// existence. The special arrays are left alone.
+    pub(crate) fn read_array_element(
+        &mut self,
+        array: &str,
+        path: &[String],
+        key: &str,
+    ) -> Result<Value> {
+        let value = self.get_array_element(array, path, key)?;
+        let special = path.is_empty()
+            && !self.array_aliases.contains_key(array)
+            && (array == "ENVIRON" || (array == "PROCINFO" && self.dialect().gawk_extensions()));
+        if matches!(value, Value::Uninitialized) && !special {
+            let target = self.lookup_array_mut(array, path)?;
+            if !target.contains_key(key) {
+                target.insert(key.to_string(), Value::Uninitialized);
+            }
+        }
+        Ok(value)
     }
 
-    pub(crate) fn array_key_exists(&self, array: &str, key: &str) -> bool {
-        let array = self.resolve_array_name(array);
+    pub(crate) fn set_array_element(
+        &mut self,
+        array: &str,
+        path: &[String],
+        key: &str,
+        value: Value,
+    ) -> Result<()> {
+        let target = self.lookup_array_mut(array, path)?;
+        if let Some(Element::Array(_)) = target.get(key) {
+            return Err(Error::runtime(element_as_scalar(array, path, key)));
+        }
+        target.insert(key.to_string(), value);
+        Ok(())
+    }
 
+    pub(crate) fn array_key_exists(&self, array: &str, path: &[String], key: &str) -> Result<bool> {
         // Check special arrays
-        match array {
-            "ARGV" => key
-                .parse::<usize>()
-                .ok()
-                .map(|i| i < self.argv.len())
-                .unwrap_or(false),
-            "ENVIRON" => self.environ.contains_key(key),
-            _ => self
-                .arrays
-                .get(array)
-                .map(|arr| arr.contains_key(key))
-                .unwrap_or(false),
+        if path.is_empty() && array == "ENVIRON" && !self.array_aliases.contains_key(array) {
+            return Ok(self.environ.contains_key(key));
         }
+        Ok(self
+            .lookup_array(array, path)?
+            .is_some_and(|arr| arr.contains_key(key)))
     }
 
-    pub(crate) fn delete_array_element(&mut self, array: &str, key: &str) {
-        let array = self.resolve_array_name(array).to_string();
-        if let Some(arr) = self.arrays.get_mut(&array) {
-            arr.remove(key);
+    /// Delete one element, which may be a subarray
+    pub(crate) fn delete_array_element(
+        &mut self,
+        array: &str,
+        path: &[String],
+        key: &str,
+    ) -> Result<()> {
+        if self.lookup_array(array, path)?.is_some() {
+            self.lookup_array_mut(array, path)?.remove(key);
         }
+        Ok(())
+    }
+
+    /// Delete every element of an array or subarray
+    pub(crate) fn clear_array(&mut self, array: &str, path: &[String]) -> Result<()> {
+        if self.lookup_array(array, path)?.is_some() {
+            self.lookup_array_mut(array, path)?.clear();
+        }
+        Ok(())
     }
 
     pub(crate) fn make_array_key(&self, indices: &[Value]) -> String {
@@ -828,6 +1240,52 @@ impl<'a> Interpreter<'a> {
     }
 }
 
+/// Message for indexing a scalar (variable or element) as if it were an array
+fn scalar_as_array(array: &str, path: &[String]) -> String {
+    format!(
+        "attempt to use scalar `{}' as an array",
+        element_name(array, path)
+    )
+}
+
+/// Message for using an array or subarray where a scalar is needed
+pub(crate) fn array_as_scalar(array: &str, path: &[String]) -> String {
+    format!(
+        "attempt to use array `{}' in a scalar context",
+        element_name(array, path)
+    )
+}
+
+/// Message for using the subarray at `key` where a scalar is needed
+fn element_as_scalar(array: &str, path: &[String], key: &str) -> String {
+    let mut path = path.to_vec();
+    path.push(key.to_string());
+    array_as_scalar(array, &path)
+}
+
+impl Drop for Interpreter<'_> {
+    fn drop(&mut self) {
+        // Reap children left behind when the program stopped with an error
+        self.close_all_streams();
+    }
+}
+
+/// Split a command-line operand of the form `name=value` into its parts
+///
+/// Returns `None` unless the text before `=` is a valid variable name.
+pub fn split_assignment(arg: &str) -> Option<(&str, &str)> {
+    let (name, value) = arg.split_once('=')?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    if (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        Some((name, value))
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1197,4 +1655,15 @@ mod tests {
         // First paragraph has 4 words across 2 lines, second has 2 words
         assert!(output.contains("1"));
     }
+
+    #[test]
+    fn test_split_assignment() {
+        assert_eq!(split_assignment("x=1"), Some(("x", "1")));
+        assert_eq!(split_assignment("_a1=b=c"), Some(("_a1", "b=c")));
+        assert_eq!(split_assignment("FS="), Some(("FS", "")));
+        assert_eq!(split_assignment("./x=1"), None);
+        assert_eq!(split_assignment("1x=1"), None);
+        assert_eq!(split_assignment("=1"), None);
+        assert_eq!(split_assignment("file.txt"), None);
+    }
 }
@@ -0,0 +1,448 @@
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, ErrorKind, Stdin};
+use std::rc::Rc;
+
+use regex::bytes::Regex;
+
+use crate::dialect::Dialect;
+use crate::error::{Error, Result};
+
+use super::ere;
+
+/// How the input stream is split into records, derived from RS
+enum Separator {
+    /// Single-byte RS (including the default "\n")
+    Byte(u8),
+    /// Multi-character RS, treated as a regular expression (gawk extension)
+    Regex(Regex),
+    /// RS = "": records are separated by one or more blank lines
+    Paragraph(Regex),
+}
+
+impl Separator {
+    fn new(rs: &str, single_char: bool, ignore_case: bool) -> Result<Self> {
+        if rs.is_empty() {
+            let re = Regex::new(r"\r?\n(?:[ \t\r]*\n)+").map_err(Error::Regex)?;
+            return Ok(Separator::Paragraph(re));
+        }
+
+        // POSIX only honours the first character of RS
+        let rs = if single_char {
+            let end = rs.chars().next().map(|c| c.len_utf8()).unwrap_or(0);
+            &rs[..end]
+        } else {
+            rs
+        };
+
+        // A single letter still needs a regex to match either case
+        if rs.len() == 1 && !(ignore_case && rs.as_bytes()[0].is_ascii_alphabetic()) {
+            return Ok(Separator::Byte(rs.as_bytes()[0]));
+        }
+
+        // A single multi-byte character is still a literal separator
+        let mut pattern = if rs.chars().count() == 1 {
+            regex::escape(rs)
+        } else {
+            ere::translate(rs, Dialect::Gawk)?
+        };
+        if ignore_case {
+            pattern.insert_str(0, "(?i)");
+        }
+        let re = Regex::new(&pattern).map_err(|e| {
+            Error::runtime(format!("invalid record separator regex \"{}\": {}", rs, e))
+        })?;
+        Ok(Separator::Regex(re))
+    }
+}
+
+/// Pending input size from which a failed separator search is only repeated
+/// once the pending input has doubled
+///
+/// Searching again after every chunk of a long record would take quadratic
+/// time; shorter records are searched as soon as more input arrives, so
+/// interactive input is never held back.
+const RESCAN_THRESHOLD: usize = 64 * 1024;
+
+/// A single input record together with the text that terminated it (RT)
+pub(crate) struct Record {
+    pub(crate) text: String,
+    pub(crate) terminator: String,
+}
+
+/// Reads records from an input stream according to the current RS
+///
+/// The separator is looked up on every call, so assigning RS in the middle of
+/// the input takes effect from the next record on. Data read ahead while
+/// searching for a separator is kept in `pending` and reused after RS changes.
+pub(crate) struct RecordReader<R> {
+    input: R,
+    /// Bytes read from `input` that have not been returned in a record yet
+    pending: Vec<u8>,
+    eof: bool,
+    /// RS value (and POSIX and IGNORECASE flags) the cached separator was built from
+    rs: Option<(String, bool, bool)>,
+    separator: Separator,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            input,
+            pending: Vec::new(),
+            eof: false,
+            rs: None,
+            separator: Separator::Byte(b'\n'),
+        }
+    }
+
+    /// Read the next record using `rs` as the record separator
+    ///
+    /// When `single_char` is set only the first character of RS is used, as
+    /// POSIX requires; `ignore_case` implements IGNORECASE. Returns `None` at
+    /// end of input.
+    pub(crate) fn read_record(
+        &mut self,
+        rs: &str,
+        single_char: bool,
+        ignore_case: bool,
+    ) -> Result<Option<Record>> {
+        let cached = matches!(&self.rs, Some((prev, single, icase))
+            if prev == rs && *single == single_char && *icase == ignore_case);
+        if !cached {
+            self.separator = Separator::new(rs, single_char, ignore_case)?;
+            self.rs = Some((rs.to_string(), single_char, ignore_case));
+        }
+
+        let record = match &self.separator {
+            Separator::Byte(sep) => {
+                let sep = *sep;
+                self.read_until_byte(sep)?
+                    .map(|(mut text, mut terminator)| {
+                        // Tolerate CRLF line endings with the default RS
+                        if sep == b'\n' && text.last() == Some(&b'\r') {
+                            text.pop();
+                            terminator.insert(0, b'\r');
+                        }
+                        (text, terminator)
+                    })
+            }
+            Separator::Regex(re) => {
+                let re = re.clone();
+                self.read_until_regex(&re)?
+            }
+            Separator::Paragraph(re) => {
+                let re = re.clone();
+                self.read_paragraph(&re)?
+            }
+        };
+
+        Ok(record.map(|(text, terminator)| Record {
+            text: String::from_utf8_lossy(&text).into_owned(),
+            terminator: String::from_utf8_lossy(&terminator).into_owned(),
+        }))
+    }
+
+    fn read_until_byte(&mut self, sep: u8) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        if let Some(pos) = self.pending.iter().position(|&b| b == sep) {
+            let mut text: Vec<u8> = self.pending.drain(..=pos).collect();
+            text.pop();
+            return Ok(Some((text, vec![sep])));
+        }
+
+        let mut text = std::mem::take(&mut self.pending);
+        if !self.eof {
+            self.input.read_until(sep, &mut text).map_err(Error::Io)?;
+            if text.last() == Some(&sep) {
+                text.pop();
+                return Ok(Some((text, vec![sep])));
+            }
+            self.eof = true;
+        }
+
+        if text.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((text, Vec::new())))
+        }
+    }
+
+    fn read_until_regex(&mut self, re: &Regex) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        loop {
+            if let Some(m) = re.find_iter(&self.pending).find(|m| !m.is_empty()) {
+                // A match touching the end of the buffer might extend further
+                // once more input arrives, so only accept it if it cannot
+                if m.end() < self.pending.len() || self.eof {
+                    let (start, end) = (m.start(), m.end());
+                    let mut text: Vec<u8> = self.pending.drain(..end).collect();
+                    let terminator = text.split_off(start);
+                    return Ok(Some((text, terminator)));
+                }
+            }
+
+            if self.eof {
+                if self.pending.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some((std::mem::take(&mut self.pending), Vec::new())));
+            }
+
+            let searched = self.pending.len();
+            loop {
+                self.fill()?;
+                if self.eof || searched < RESCAN_THRESHOLD || self.pending.len() >= 2 * searched {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn read_paragraph(&mut self, re: &Regex) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        // Skip blank lines before the start of the record
+        loop {
+            let content = self
+                .pending
+                .iter()
+                .position(|b| !matches!(b, b'\n' | b' ' | b'\t' | b'\r'));
+            match content {
+                Some(pos) => {
+                    let skip = self.pending[..pos]
+                        .iter()
+                        .rposition(|&b| b == b'\n')
+                        .map_or(0, |p| p + 1);
+                    self.pending.drain(..skip);
+                    break;
+                }
+                None if self.eof => {
+                    self.pending.clear();
+                    return Ok(None);
+                }
+                None => self.fill()?,
+            }
+        }
+
+        let record = self.read_until_regex(re)?;
+
+        // The last record may still be followed by trailing newlines, which
+        // then become its terminator
+        Ok(record.map(|(mut text, terminator)| {
+            if !terminator.is_empty() {
+                return (text, terminator);
+            }
+            let mut end = text.len();
+            if let Some(last) = text
+                .iter()
+                .rposition(|b| !matches!(b, b'\n' | b' ' | b'\t' | b'\r'))
+            {
+                end = text[last..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(text.len(), |p| last + p);
+                if end > 0 && text[end - 1] == b'\r' {
+                    end -= 1;
+                }
+            }
+            let terminator = text.split_off(end);
+            (text, terminator)
+        }))
+    }
+
+    /// Append the next chunk of input to `pending`
+    fn fill(&mut self) -> Result<()> {
+        let len = loop {
+            match self.input.fill_buf() {
+                Ok(buf) => {
+                    self.pending.extend_from_slice(buf);
+                    break buf.len();
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::Io(e)),
+            }
+        };
+        if len == 0 {
+            self.eof = true;
+        }
+        self.input.consume(len);
+        Ok(())
+    }
+}
+
+/// Standard input, shared by the main input and `getline < "-"`
+///
+/// Data read ahead from standard input belongs to whichever reads the next
+/// record, so both must go through the same reader.
+pub(crate) type SharedStdin = Rc<RefCell<RecordReader<BufReader<Stdin>>>>;
+
+/// Where records are read from: a reader of its own or the shared stdin
+pub(crate) enum RecordSource<R> {
+    Reader(RecordReader<R>),
+    Stdin(SharedStdin),
+}
+
+impl<R: BufRead> RecordSource<R> {
+    pub(crate) fn new(input: R) -> Self {
+        RecordSource::Reader(RecordReader::new(input))
+    }
+
+    /// Read the next record, as `RecordReader::read_record` does
+    pub(crate) fn read_record(
+        &mut self,
+        rs: &str,
+        single_char: bool,
+        ignore_case: bool,
+    ) -> Result<Option<Record>> {
+        match self {
+            RecordSource::Reader(reader) => reader.read_record(rs, single_char, ignore_case),
+            RecordSource::Stdin(stdin) => {
+                stdin.borrow_mut().read_record(rs, single_char, ignore_case)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    /// Read all records, using a tiny buffer to exercise buffer boundaries
+    fn records(input: &str, rs: &str) -> Vec<String> {
+        let mut reader = RecordReader::new(BufReader::with_capacity(3, Cursor::new(input)));
+        let mut result = Vec::new();
+        while let Some(record) = reader.read_record(rs, false, false).unwrap() {
+            result.push(record.text);
+        }
+        result
+    }
+
+    #[test]
+    fn test_newline_records() {
+        assert_eq!(records("a\nb\n\nc", "\n"), vec!["a", "b", "", "c"]);
+        assert_eq!(records("a\r\nb\r\n", "\n"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_single_char_records() {
+        assert_eq!(records("a;b;c;", ";"), vec!["a", "b", "c"]);
+        assert_eq!(records("a\0b", "\0"), vec!["a", "b"]);
+        assert_eq!(records("a|b", "|"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_regex_records_across_buffers() {
+        assert_eq!(
+            records("one\r\ntwo\nthree", "\r\n|\n"),
+            vec!["one", "two", "three"]
+        );
+        assert_eq!(records("aXXXXXbXc", "X+"), vec!["a", "b", "c"]);
+        assert_eq!(records("a<sep>b<sep>", "<sep>"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_long_record_with_regex_separator() {
+        // One 8 MB record; each chunk read must not rescan the whole record
+        let long = "a".repeat(8 << 20);
+        let input = format!("{}XXXXb", long);
+        let mut reader = RecordReader::new(BufReader::new(Cursor::new(input)));
+        let record = reader.read_record("X+", false, false).unwrap().unwrap();
+        assert_eq!(record.text.len(), long.len());
+        assert_eq!(record.terminator, "XXXX");
+        let record = reader.read_record("X+", false, false).unwrap().unwrap();
+        assert_eq!(record.text, "b");
+    }
+
+    #[test]
+    fn test_paragraph_records() {
+        assert_eq!(
+            records("\n\na\nb\n\n\n\nc\n", ""),
+            vec!["a\nb".to_string(), "c".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_record_terminators() {
+        let mut reader = RecordReader::new(Cursor::new("a\r\nb;;c"));
+        let rt = |r: Option<Record>| r.map(|r| (r.text, r.terminator)).unwrap();
+        assert_eq!(
+            rt(reader.read_record("\n", false, false).unwrap()),
+            ("a".to_string(), "\r\n".to_string())
+        );
+        assert_eq!(
+            rt(reader.read_record(";+", false, false).unwrap()),
+            ("b".to_string(), ";;".to_string())
+        );
+        assert_eq!(
+            rt(reader.read_record(";+", false, false).unwrap()),
+            ("c".to_string(), String::new())
+        );
+
+        let mut reader = RecordReader::new(Cursor::new("p1\n\n\np2\n"));
+        assert_eq!(
+            rt(reader.read_record("", false, false).unwrap()),
+            ("p1".to_string(), "\n\n\n".to_string())
+        );
+        assert_eq!(
+            rt(reader.read_record("", false, false).unwrap()),
+            ("p2".to_string(), "\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_posix_uses_first_char() {
+        let mut reader = RecordReader::new(Cursor::new("a;b:c"));
+        assert_eq!(
+            reader.read_record(";:", true, false).unwrap().unwrap().text,
+            "a"
+        );
+        assert_eq!(
+            reader.read_record(";:", true, false).unwrap().unwrap().text,
+            "b:c"
+        );
+    }
+
+    #[test]
+    fn test_ignore_case_separator() {
+        let mut reader = RecordReader::new(Cursor::new("1x2X3"));
+        assert_eq!(
+            reader.read_record("x", false, true).unwrap().unwrap().text,
+            "1"
+        );
+        assert_eq!(
+            reader.read_record("x", false, true).unwrap().unwrap().text,
+            "2"
+        );
+        assert_eq!(
+            reader.read_record("x", false, false).unwrap().unwrap().text,
+            "3"
+        );
+    }
+
+    #[test]
+    fn test_rs_change_mid_stream() {
+        let mut reader = RecordReader::new(Cursor::new("a;b\nc;d\n"));
+        assert_eq!(
+            reader
+                .read_record(";+", false, false)
+                .unwrap()
+                .unwrap()
+                .text,
+            "a"
+        );
+        assert_eq!(
+            reader
+                .read_record("\n", false, false)
+                .unwrap()
+                .unwrap()
+                .text,
+            "b"
+        );
+        assert_eq!(
+            reader.read_record(";", false, false).unwrap().unwrap().text,
+            "c"
+        );
+        assert_eq!(
+            reader.read_record(";", false, false).unwrap().unwrap().text,
+            "d\n"
+        );
+        assert!(reader.read_record(";", false, false).unwrap().is_none());
+    }
+}
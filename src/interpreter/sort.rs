@@ -0,0 +1,250 @@
+//! Array orders: gawk's `PROCINFO["sorted_in"]` and the `how` argument of
+//! `asort`/`asorti`
+
+use std::cmp::Ordering;
+use std::io::Write;
+
+use super::Interpreter;
+use super::array::Element;
+use super::builtins::Argument;
+use crate::error::{Error, Result};
+use crate::value::{Value, compare_values, parse_leading_number};
+
+/// How the elements of an array are ordered
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SortOrder {
+    /// The order the array keeps its elements in (`@unsorted`)
+    Unsorted,
+    /// One of gawk's predefined orders, such as `@ind_num_desc`
+    Predefined { key: SortKey, descending: bool },
+    /// A user-defined function `cmp(i1, v1, i2, v2)` returning <0, 0 or >0
+    Function(String),
+}
+
+/// What a predefined order compares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SortKey {
+    /// Indices as strings
+    IndexString,
+    /// Indices as numbers
+    IndexNumber,
+    /// Values by type (numbers, then strings, then subarrays), then value
+    ValueType,
+    /// Values as strings
+    ValueString,
+    /// Values as numbers
+    ValueNumber,
+}
+
+impl SortOrder {
+    /// The order a `PROCINFO["sorted_in"]` value names
+    ///
+    /// Anything other than a predefined `@...` order is taken to be the name
+    /// of a comparison function.
+    pub fn from_name(name: &str) -> SortOrder {
+        let (key, descending) = match name {
+            "" | "@unsorted" => return SortOrder::Unsorted,
+            "@ind_str_asc" => (SortKey::IndexString, false),
+            "@ind_str_desc" => (SortKey::IndexString, true),
+            "@ind_num_asc" => (SortKey::IndexNumber, false),
+            "@ind_num_desc" => (SortKey::IndexNumber, true),
+            "@val_type_asc" => (SortKey::ValueType, false),
+            "@val_type_desc" => (SortKey::ValueType, true),
+            "@val_str_asc" => (SortKey::ValueString, false),
+            "@val_str_desc" => (SortKey::ValueString, true),
+            "@val_num_asc" => (SortKey::ValueNumber, false),
+            "@val_num_desc" => (SortKey::ValueNumber, true),
+            _ => return SortOrder::Function(name.to_string()),
+        };
+        SortOrder::Predefined { key, descending }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// The order `PROCINFO["sorted_in"]` asks `for (k in a)` to use
+    pub(crate) fn sorted_in(&self) -> SortOrder {
+        if !self.dialect().gawk_extensions() {
+            return SortOrder::Unsorted;
+        }
+        match self.arrays.get("PROCINFO").and_then(|a| a.get("sorted_in")) {
+            Some(Element::Scalar(value)) => SortOrder::from_name(&value.as_str()),
+            _ => SortOrder::Unsorted,
+        }
+    }
+
+    /// The indices of an array or subarray in the given order
+    pub(crate) fn sorted_keys<W: Write>(
+        &mut self,
+        array: &str,
+        path: &[String],
+        order: &SortOrder,
+        output: &mut W,
+    ) -> Result<Vec<String>> {
+        let Some(elements) = self.lookup_array(array, path)? else {
+            return Ok(Vec::new());
+        };
+
+        match order {
+            SortOrder::Unsorted => Ok(elements.keys().cloned().collect()),
+            SortOrder::Predefined { key, descending } => {
+                // NaN values make `compare_values` inconsistent, which
+                // `merge_sort` tolerates
+                let entries: Vec<_> = elements.iter().collect();
+                let sorted = merge_sort(entries, &mut |a, b| {
+                    let ordering = compare_entries(*key, a, b);
+                    Ok(if *descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    })
+                })?;
+                Ok(sorted.into_iter().map(|(k, _)| k.clone()).collect())
+            }
+            SortOrder::Function(name) => {
+                let func = *self.functions.get(name).ok_or_else(|| {
+                    Error::runtime(format!(
+                        "sort comparison function `{}' is not defined",
+                        name
+                    ))
+                })?;
+                let entries: Vec<(String, Argument)> = elements
+                    .iter()
+                    .map(|(k, element)| {
+                        let value = match element {
+                            Element::Scalar(value) => Argument::Value(value.clone()),
+                            Element::Array(_) => {
+                                let mut path = path.to_vec();
+                                path.push(k.clone());
+                                Argument::Array(self.array_ref(array, path))
+                            }
+                        };
+                        (k.clone(), value)
+                    })
+                    .collect();
+
+                let sorted = merge_sort(entries, &mut |(i1, v1), (i2, v2)| {
+                    let args = vec![
+                        Argument::Value(Value::from_input(i1.clone())),
+                        v1.clone(),
+                        Argument::Value(Value::from_input(i2.clone())),
+                        v2.clone(),
+                    ];
+                    let result = self.call_user_function(func, args, output)?;
+                    Ok(result
+                        .to_number()
+                        .partial_cmp(&0.0)
+                        .unwrap_or(Ordering::Equal))
+                })?;
+                Ok(sorted.into_iter().map(|(k, _)| k).collect())
+            }
+        }
+    }
+}
+
+/// Compare two elements for a predefined order, in ascending direction
+///
+/// Elements that compare equal are ordered by index, so the result does not
+/// depend on how the array stores them.
+fn compare_entries(key: SortKey, a: &(&String, &Element), b: &(&String, &Element)) -> Ordering {
+    let (i1, e1) = a;
+    let (i2, e2) = b;
+    let by_value = match key {
+        SortKey::IndexString => Ordering::Equal,
+        SortKey::IndexNumber => parse_leading_number(i1).total_cmp(&parse_leading_number(i2)),
+        SortKey::ValueType => type_rank(e1)
+            .cmp(&type_rank(e2))
+            .then_with(|| match (e1, e2) {
+                (Element::Scalar(v1), Element::Scalar(v2)) => compare_values(v1, v2),
+                _ => Ordering::Equal,
+            }),
+        SortKey::ValueString => scalars_first(e1, e2, |v1, v2| v1.as_str().cmp(&v2.as_str())),
+        SortKey::ValueNumber => scalars_first(e1, e2, |v1, v2| {
+            v1.to_number()
+                .total_cmp(&v2.to_number())
+                .then_with(|| v1.as_str().cmp(&v2.as_str()))
+        }),
+    };
+    by_value.then_with(|| i1.cmp(i2))
+}
+
+/// Numbers (and untyped values) first, then strings, then subarrays
+fn type_rank(element: &Element) -> u8 {
+    match element {
+        Element::Scalar(Value::String(_) | Value::Regex(_)) => 1,
+        Element::Scalar(_) => 0,
+        Element::Array(_) => 2,
+    }
+}
+
+/// Compare scalar values with `compare`, placing subarrays after them
+fn scalars_first(
+    e1: &Element,
+    e2: &Element,
+    compare: impl FnOnce(&Value, &Value) -> Ordering,
+) -> Ordering {
+    match (e1, e2) {
+        (Element::Scalar(v1), Element::Scalar(v2)) => compare(v1, v2),
+        (Element::Scalar(_), Element::Array(_)) => Ordering::Less,
+        (Element::Array(_), Element::Scalar(_)) => Ordering::Greater,
+        (Element::Array(_), Element::Array(_)) => Ordering::Equal,
+    }
+}
+
+/// A stable merge sort whose comparison may fail
+///
+/// Unlike `slice::sort_by`, it copes with user-defined comparison functions
+/// that are not a consistent total order.
+fn merge_sort<T>(
+    mut items: Vec<T>,
+    compare: &mut impl FnMut(&T, &T) -> Result<Ordering>,
+) -> Result<Vec<T>> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if compare(r, l)? == Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(SortOrder::from_name("@unsorted"), SortOrder::Unsorted);
+        assert_eq!(
+            SortOrder::from_name("@ind_num_desc"),
+            SortOrder::Predefined {
+                key: SortKey::IndexNumber,
+                descending: true
+            }
+        );
+        assert_eq!(
+            SortOrder::from_name("by_length"),
+            SortOrder::Function("by_length".to_string())
+        );
+    }
+
+    #[test]
+    fn test_merge_sort_is_stable() {
+        let items = vec![(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd')];
+        let sorted = merge_sort(items, &mut |a, b| Ok(a.0.cmp(&b.0))).unwrap();
+        assert_eq!(sorted, vec![(1, 'b'), (1, 'd'), (2, 'a'), (2, 'c')]);
+
+        let err = merge_sort(vec![1, 2], &mut |_, _| Err(Error::runtime("boom")));
+        assert!(err.is_err());
+    }
+}
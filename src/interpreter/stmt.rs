@@ -1,12 +1,14 @@
+use std::cmp::Ordering;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::process::{Command, Stdio};
+use std::process::{ChildStdin, Command, Stdio};
 
 use crate::ast::*;
 use crate::error::{Error, Result};
 use crate::value::Value;
 
-use super::{Interpreter, OutputFile};
+use super::coprocess::Coprocess;
+use super::{Interpreter, OutputFile, at_location, is_stderr_name, is_stdout_name, open_fd_name};
 
 /// Result of executing a statement
 pub enum StmtResult {
@@ -14,6 +16,8 @@ pub enum StmtResult {
     Break,
     Continue,
     Return(Value),
+    /// `next`, `nextfile` or `exit`: abandon the rest of the action
+    Abort,
 }
 
 impl<'a> Interpreter<'a> {
@@ -21,6 +25,8 @@ impl<'a> Interpreter<'a> {
         for stmt in &block.statements {
             let result = self.execute_stmt(stmt, output)?;
             match result {
+                // A function called from this statement may have run exit
+                StmtResult::Normal if self.should_exit => return Ok(StmtResult::Abort),
                 StmtResult::Normal => continue,
                 other => return Ok(other),
             }
@@ -28,6 +34,19 @@ impl<'a> Interpreter<'a> {
         Ok(StmtResult::Normal)
     }
 
+    /// Whether a switch subject selects a case
+    fn case_matches(&mut self, label: &CaseLabel, value: &Value) -> Result<bool> {
+        Ok(match label {
+            CaseLabel::Number(n) => self.compare(value, &Value::Number(*n)) == Ordering::Equal,
+            CaseLabel::String(s) => {
+                let label = Value::from_string(s.clone());
+                self.compare(&Value::from_string(value.to_string_val()), &label) == Ordering::Equal
+            }
+            CaseLabel::Regex(pattern) => self.get_regex(pattern)?.is_match(&value.as_str()),
+            CaseLabel::Default => false,
+        })
+    }
+
     pub fn execute_stmt<W: Write>(&mut self, stmt: &Stmt, output: &mut W) -> Result<StmtResult> {
         match stmt {
             Stmt::Empty => Ok(StmtResult::Normal),
@@ -83,7 +102,7 @@ impl<'a> Interpreter<'a> {
                     match self.execute_stmt(body, output)? {
                         StmtResult::Normal | StmtResult::Continue => continue,
                         StmtResult::Break => break,
-                        StmtResult::Return(v) => return Ok(StmtResult::Return(v)),
+                        result @ (StmtResult::Return(_) | StmtResult::Abort) => return Ok(result),
                     }
                 }
                 Ok(StmtResult::Normal)
@@ -96,7 +115,7 @@ impl<'a> Interpreter<'a> {
                     match self.execute_stmt(body, output)? {
                         StmtResult::Normal | StmtResult::Continue => {}
                         StmtResult::Break => break,
-                        StmtResult::Return(v) => return Ok(StmtResult::Return(v)),
+                        result @ (StmtResult::Return(_) | StmtResult::Abort) => return Ok(result),
                     }
                     let cond = self.eval_expr_with_output(condition, output)?;
                     if !cond.is_truthy() {
@@ -131,7 +150,7 @@ impl<'a> Interpreter<'a> {
                     match self.execute_stmt(body, output)? {
                         StmtResult::Normal | StmtResult::Continue => {}
                         StmtResult::Break => break,
-                        StmtResult::Return(v) => return Ok(StmtResult::Return(v)),
+                        result @ (StmtResult::Return(_) | StmtResult::Abort) => return Ok(result),
                     }
 
                     // Execute update
@@ -143,26 +162,50 @@ impl<'a> Interpreter<'a> {
             }
 
             Stmt::ForIn {
-                var, array, body, ..
+                var,
+                array,
+                path,
+                body,
+                location,
             } => {
-                // Get keys from array (resolve aliases for pass-by-reference)
-                let resolved_array = self
-                    .array_aliases
-                    .get(array)
-                    .map(|s| s.as_str())
-                    .unwrap_or(array);
-                let keys: Vec<String> = self
-                    .arrays
-                    .get(resolved_array)
-                    .map(|arr| arr.keys().cloned().collect())
-                    .unwrap_or_default();
+                let path = self.eval_path(path, output)?;
+                let order = self.sorted_in();
+                let keys = self
+                    .sorted_keys(array, &path, &order, output)
+                    .map_err(|e| at_location(e, location))?;
 
                 for key in keys {
-                    self.set_variable_value(var, Value::from_string(key));
+                    self.set_variable_value(var, Value::from_input(key));
                     match self.execute_stmt(body, output)? {
                         StmtResult::Normal | StmtResult::Continue => continue,
                         StmtResult::Break => break,
-                        StmtResult::Return(v) => return Ok(StmtResult::Return(v)),
+                        result @ (StmtResult::Return(_) | StmtResult::Abort) => return Ok(result),
+                    }
+                }
+                Ok(StmtResult::Normal)
+            }
+
+            Stmt::Switch { subject, cases, .. } => {
+                let value = self.eval_expr_with_output(subject, output)?;
+                let mut start = None;
+                for (i, case) in cases.iter().enumerate() {
+                    if self.case_matches(&case.label, &value)? {
+                        start = Some(i);
+                        break;
+                    }
+                }
+                let start =
+                    start.or_else(|| cases.iter().position(|c| c.label == CaseLabel::Default));
+
+                // Run from the matching case to a break or the end of the switch
+                if let Some(start) = start {
+                    for stmt in cases[start..].iter().flat_map(|c| &c.body) {
+                        match self.execute_stmt(stmt, output)? {
+                            StmtResult::Normal if self.should_exit => return Ok(StmtResult::Abort),
+                            StmtResult::Normal => {}
+                            StmtResult::Break => break,
+                            result => return Ok(result),
+                        }
                     }
                 }
                 Ok(StmtResult::Normal)
@@ -176,12 +219,12 @@ impl<'a> Interpreter<'a> {
 
             Stmt::Next { .. } => {
                 self.should_next = true;
-                Ok(StmtResult::Normal)
+                Ok(StmtResult::Abort)
             }
 
             Stmt::Nextfile { .. } => {
                 self.should_nextfile = true;
-                Ok(StmtResult::Normal)
+                Ok(StmtResult::Abort)
             }
 
             Stmt::Exit { code, .. } => {
@@ -192,9 +235,10 @@ impl<'a> Interpreter<'a> {
                             .map(|v| v.to_number() as i32)
                     })
                     .transpose()?
-                    .unwrap_or(0);
+                    // A bare exit in END keeps the status of an earlier exit
+                    .unwrap_or(self.exit_code);
                 self.should_exit = true;
-                Ok(StmtResult::Normal)
+                Ok(StmtResult::Abort)
             }
 
             Stmt::Return { value, .. } => {
@@ -206,17 +250,25 @@ impl<'a> Interpreter<'a> {
                 Ok(StmtResult::Return(val))
             }
 
-            Stmt::Delete { array, index, .. } => {
+            Stmt::Delete {
+                array,
+                path,
+                index,
+                location,
+            } => {
+                let path = self.eval_path(path, output)?;
                 if index.is_empty() {
                     // delete array (entire array)
-                    self.arrays.remove(array);
+                    self.clear_array(array, &path)
+                        .map_err(|e| at_location(e, location))?;
                 } else {
                     let key_parts: Result<Vec<Value>> = index
                         .iter()
                         .map(|e| self.eval_expr_with_output(e, output))
                         .collect();
                     let key = self.make_array_key(&key_parts?);
-                    self.delete_array_element(array, &key);
+                    self.delete_array_element(array, &path, &key)
+                        .map_err(|e| at_location(e, location))?;
                 }
                 Ok(StmtResult::Normal)
             }
@@ -227,7 +279,7 @@ impl<'a> Interpreter<'a> {
                 location,
             } => {
                 // Getline as a statement
-                let _result = self.eval_getline(var.as_ref(), input.as_ref(), *location)?;
+                let _result = self.eval_getline(var.as_ref(), input.as_ref(), location.clone())?;
                 Ok(StmtResult::Normal)
             }
         }
@@ -255,35 +307,7 @@ impl<'a> Interpreter<'a> {
             values.join(&self.ofs)
         };
 
-        // Handle output redirection
-        match redirect {
-            None => {
-                writeln!(default_output, "{}", line).map_err(Error::Io)?;
-            }
-            Some(OutputRedirect::Truncate(target_expr)) => {
-                let filename = self
-                    .eval_expr_with_output(target_expr, default_output)?
-                    .to_string_val();
-                let file = self.get_or_open_file(&filename, false)?;
-                writeln!(file, "{}", line).map_err(Error::Io)?;
-            }
-            Some(OutputRedirect::Append(target_expr)) => {
-                let filename = self
-                    .eval_expr_with_output(target_expr, default_output)?
-                    .to_string_val();
-                let file = self.get_or_open_file(&filename, true)?;
-                writeln!(file, "{}", line).map_err(Error::Io)?;
-            }
-            Some(OutputRedirect::Pipe(cmd_expr)) => {
-                let cmd = self
-                    .eval_expr_with_output(cmd_expr, default_output)?
-                    .to_string_val();
-                let pipe = self.get_or_open_pipe(&cmd)?;
-                writeln!(pipe, "{}", line).map_err(Error::Io)?;
-            }
-        }
-
-        Ok(())
+        self.write_output(redirect, &format!("{}\n", line), default_output)
     }
 
     fn execute_printf<W: Write>(
@@ -304,41 +328,65 @@ impl<'a> Interpreter<'a> {
 
         let formatted = self.format_printf(&format, &values);
 
-        // Handle output redirection
-        match redirect {
-            None => {
-                write!(default_output, "{}", formatted).map_err(Error::Io)?;
-            }
-            Some(OutputRedirect::Truncate(target_expr)) => {
+        self.write_output(redirect, &formatted, default_output)
+    }
+
+    /// Write print/printf output to the default output or its redirection
+    fn write_output<W: Write>(
+        &mut self,
+        redirect: &Option<OutputRedirect>,
+        text: &str,
+        default_output: &mut W,
+    ) -> Result<()> {
+        let out: &mut dyn Write = match redirect {
+            None => default_output,
+            Some(OutputRedirect::Truncate(target_expr))
+            | Some(OutputRedirect::Append(target_expr)) => {
+                let append = matches!(redirect, Some(OutputRedirect::Append(_)));
                 let filename = self
                     .eval_expr_with_output(target_expr, default_output)?
                     .to_string_val();
-                let file = self.get_or_open_file(&filename, false)?;
-                write!(file, "{}", formatted).map_err(Error::Io)?;
+                if is_stdout_name(&filename) {
+                    default_output
+                } else {
+                    if is_stderr_name(&filename) {
+                        // Keep buffered stdout in order with unbuffered stderr
+                        default_output.flush().map_err(Error::Io)?;
+                    }
+                    self.get_or_open_file(&filename, append)?
+                }
             }
-            Some(OutputRedirect::Append(target_expr)) => {
-                let filename = self
-                    .eval_expr_with_output(target_expr, default_output)?
+            Some(OutputRedirect::Pipe(cmd_expr)) => {
+                let cmd = self
+                    .eval_expr_with_output(cmd_expr, default_output)?
                     .to_string_val();
-                let file = self.get_or_open_file(&filename, true)?;
-                write!(file, "{}", formatted).map_err(Error::Io)?;
+                self.get_or_open_pipe(&cmd)?
             }
-            Some(OutputRedirect::Pipe(cmd_expr)) => {
+            Some(OutputRedirect::Coprocess(cmd_expr)) => {
                 let cmd = self
                     .eval_expr_with_output(cmd_expr, default_output)?
                     .to_string_val();
-                let pipe = self.get_or_open_pipe(&cmd)?;
-                write!(pipe, "{}", formatted).map_err(Error::Io)?;
+                self.get_or_open_coprocess(&cmd)?
             }
-        }
-
-        Ok(())
+        };
+        out.write_all(text.as_bytes()).map_err(Error::Io)
     }
 
     /// Get or open a file for output redirection
+    ///
+    /// `/dev/stderr` is the process's standard error and other `/dev/fd/N`
+    /// names write to the inherited descriptor without truncating it.
     fn get_or_open_file(&mut self, filename: &str, append: bool) -> Result<&mut OutputFile> {
         if !self.output_files.contains_key(filename) {
-            let file = if append {
+            if is_stderr_name(filename) {
+                self.output_files
+                    .insert(filename.to_string(), OutputFile::Stderr(std::io::stderr()));
+                return Ok(self.output_files.get_mut(filename).unwrap());
+            }
+
+            let file = if let Some(file) = open_fd_name(filename) {
+                file.map_err(Error::Io)?
+            } else if append {
                 OpenOptions::new()
                     .create(true)
                     .append(true)
@@ -356,20 +404,29 @@ impl<'a> Interpreter<'a> {
     /// Get or open a pipe for output redirection
     fn get_or_open_pipe(&mut self, cmd: &str) -> Result<&mut OutputFile> {
         if !self.output_files.contains_key(cmd) {
-            let child = Command::new("sh")
+            let mut child = Command::new("sh")
                 .arg("-c")
                 .arg(cmd)
                 .stdin(Stdio::piped())
                 .spawn()
                 .map_err(Error::Io)?;
 
-            let stdin = child.stdin.unwrap();
+            let stdin = child.stdin.take().unwrap();
             self.output_files
-                .insert(cmd.to_string(), OutputFile::Pipe(stdin));
+                .insert(cmd.to_string(), OutputFile::Pipe { stdin, child });
         }
         Ok(self.output_files.get_mut(cmd).unwrap())
     }
 
+    /// Get or start a coprocess and return the pipe to its standard input
+    fn get_or_open_coprocess(&mut self, cmd: &str) -> Result<&mut ChildStdin> {
+        if !self.coprocesses.contains_key(cmd) {
+            let coprocess = Coprocess::spawn(cmd)?;
+            self.coprocesses.insert(cmd.to_string(), coprocess);
+        }
+        self.coprocesses.get_mut(cmd).unwrap().writer()
+    }
+
     pub(crate) fn format_printf(&self, format: &str, args: &[Value]) -> String {
         let mut result = String::new();
         let mut chars = format.chars().peekable();
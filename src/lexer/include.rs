@@ -0,0 +1,225 @@
+//! `@include` directive support (gawk extension)
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{Lexer, Token, TokenKind};
+use crate::error::{Error, Result};
+
+/// Search path used when AWKPATH is not set
+const DEFAULT_AWKPATH: &str = ".:/usr/local/share/awk";
+
+/// Source files pulled in with `@include`
+///
+/// Each file is included at most once; including a file that is still being
+/// read is a cycle and reported as an error.
+#[derive(Default)]
+pub(super) struct Includes {
+    /// Canonical paths of every file read so far
+    seen: HashSet<PathBuf>,
+    /// Canonical paths of the files currently being read, innermost last
+    active: Vec<PathBuf>,
+}
+
+impl Includes {
+    /// Finish reading a top-level source file
+    pub(super) fn end_file(&mut self) {
+        self.active.clear();
+    }
+}
+
+impl<'a> Lexer<'a> {
+    /// Name the source file, so token locations and `@include` searches use it
+    pub fn set_file_name(&mut self, name: &str) {
+        let path = Path::new(name);
+        self.file = Some(Arc::from(name));
+        self.dir = path.parent().map(Path::to_path_buf);
+        if let Ok(canonical) = path.canonicalize() {
+            self.includes.seen.insert(canonical.clone());
+            self.includes.active.push(canonical);
+        }
+    }
+
+    /// Tokenize a library file as if by `@include` (the `-i` option)
+    ///
+    /// The returned tokens have no end-of-file token, so they can be placed in
+    /// front of the tokens of the main program.
+    pub fn include_file(&mut self, name: &str) -> Result<Vec<Token>> {
+        self.include(name, |message| {
+            Error::Io(io::Error::new(io::ErrorKind::NotFound, message))
+        })
+    }
+
+    /// Handle `@include "file"`; the `@include` itself has been consumed
+    pub(super) fn include_directive(&mut self, line: usize, col: usize) -> Result<Vec<Token>> {
+        while matches!(self.peek_char(), Some((_, ' ' | '\t'))) {
+            self.advance();
+        }
+        if !matches!(self.peek_char(), Some((_, '"'))) {
+            return Err(Error::lexer(
+                "@include requires a quoted file name",
+                line,
+                col,
+            ));
+        }
+        let TokenKind::String(name) = self.scan_string()?.kind else {
+            unreachable!("scan_string returns a string token");
+        };
+        self.include(&name, |message| Error::lexer(message, line, col))
+    }
+
+    /// Find and tokenize an included file
+    ///
+    /// `error` builds the error for a file that cannot be included; errors in
+    /// the included source itself carry that file's name.
+    fn include(&mut self, name: &str, error: impl Fn(String) -> Error) -> Result<Vec<Token>> {
+        let path = self
+            .find_include(name)
+            .ok_or_else(|| error(format!("cannot find include file `{}'", name)))?;
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| error(format!("cannot open include file `{}': {}", name, e)))?;
+
+        if self.includes.active.contains(&canonical) {
+            return Err(error(format!("include cycle: `{}' includes itself", name)));
+        }
+        if !self.includes.seen.insert(canonical.clone()) {
+            return Ok(Vec::new());
+        }
+
+        let source = fs::read_to_string(&path)
+            .map_err(|e| error(format!("cannot open include file `{}': {}", name, e)))?;
+
+        let mut lexer = Lexer::new(&source);
+        lexer.set_dialect(self.dialect);
+        lexer.file = Some(Arc::from(path.display().to_string()));
+        lexer.dir = path.parent().map(Path::to_path_buf);
+        lexer.includes = std::mem::take(&mut self.includes);
+        lexer.includes.active.push(canonical);
+
+        let tokens = lexer.tokenize();
+
+        lexer.includes.active.pop();
+        self.includes = std::mem::take(&mut lexer.includes);
+
+        let mut tokens = tokens?;
+        // The directive ends like a statement, in place of the end of file
+        if let Some(eof) = tokens.pop() {
+            tokens.push(Token {
+                kind: TokenKind::Newline,
+                ..eof
+            });
+        }
+        Ok(tokens)
+    }
+
+    /// Look for an include file next to the including file, then in AWKPATH
+    ///
+    /// A name without an extension is also tried with `.awk` appended.
+    fn find_include(&self, name: &str) -> Option<PathBuf> {
+        let mut dirs = Vec::new();
+        if Path::new(name).is_absolute() {
+            dirs.push(PathBuf::new());
+        } else {
+            dirs.push(self.dir.clone().unwrap_or_else(|| PathBuf::from(".")));
+            let awkpath = env::var("AWKPATH").unwrap_or_else(|_| DEFAULT_AWKPATH.to_string());
+            // An empty entry means the current directory
+            dirs.extend(
+                awkpath
+                    .split(':')
+                    .map(|dir| PathBuf::from(if dir.is_empty() { "." } else { dir })),
+            );
+        }
+
+        let with_suffix = Path::new(name).extension().is_none();
+        dirs.iter()
+            .flat_map(|dir| {
+                let suffixed = with_suffix.then(|| dir.join(format!("{}.awk", name)));
+                std::iter::once(dir.join(name)).chain(suffixed)
+            })
+            .find(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(dir: &Path, name: &str, source: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, source).unwrap();
+        path.display().to_string()
+    }
+
+    fn lex_file(path: &str) -> Result<Vec<Token>> {
+        let source = fs::read_to_string(path).unwrap();
+        let mut lexer = Lexer::new(&source);
+        lexer.set_file_name(path);
+        lexer.tokenize()
+    }
+
+    #[test]
+    fn test_include_once_from_own_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "lib.awk", "function f() { return 1 }\n");
+        let main = write(
+            dir.path(),
+            "main.awk",
+            "@include \"lib.awk\"\n@include \"lib\"\nBEGIN { print f() }\n",
+        );
+
+        let tokens = lex_file(&main).unwrap();
+        let functions = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Function)
+            .count();
+        assert_eq!(functions, 1);
+
+        let lib = tokens
+            .iter()
+            .find(|t| t.kind == TokenKind::Function)
+            .unwrap();
+        assert!(lib.location.file.as_deref().unwrap().ends_with("lib.awk"));
+        assert_eq!(lib.location.line, 1);
+        let print = tokens.iter().find(|t| t.kind == TokenKind::Print).unwrap();
+        assert_eq!(print.location.file.as_deref(), Some(main.as_str()));
+        assert_eq!(print.location.line, 3);
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "a.awk", "@include \"b.awk\"\n");
+        write(dir.path(), "b.awk", "@include \"a.awk\"\n");
+        let main = write(dir.path(), "main.awk", "@include \"a.awk\"\n");
+
+        let err = lex_file(&main).unwrap_err().to_string();
+        assert!(err.contains("include cycle: `a.awk'"), "{}", err);
+        assert!(err.contains("b.awk, line 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_include_errors_name_library_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "bad.awk", "BEGIN {\n  x = `\n}\n");
+        let main = write(dir.path(), "main.awk", "@include \"bad.awk\"\n");
+
+        let err = lex_file(&main).unwrap_err().to_string();
+        assert!(err.contains("bad.awk, line 2, column 7"), "{}", err);
+
+        let err = Lexer::new("@include \"no-such-file\"")
+            .tokenize()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("cannot find include file `no-such-file'"),
+            "{}",
+            err
+        );
+    }
+}
@@ -1,8 +1,152 @@
+mod include;
 mod tokens;
 
 pub use tokens::{Token, TokenKind, keyword_to_token};
 
-use crate::error::{Error, Result};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::dialect::Dialect;
+use crate::error::{Error, Result, SourceLocation};
+use crate::value::parse_non_decimal;
+
+use include::Includes;
+
+/// Process AWK escape sequences in text from the command line
+///
+/// Used for `-v` and operand assignments and for `-F`, which POSIX says are
+/// treated like the contents of a string literal. The error is the message
+/// for a `\x` escape outside gawk mode.
+pub fn unescape(text: &str, dialect: Dialect) -> std::result::Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('\\') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if rest.is_empty() {
+            // A trailing backslash stands for itself
+            result.push('\\');
+            break;
+        }
+        let used = decode_escape(rest, dialect, &mut result)?;
+        rest = &rest[used..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Decode the escape sequence at the start of `rest`, the text after a backslash
+///
+/// The decoded text is appended to `out` and the number of bytes of `rest`
+/// used is returned. Sequences that do not make a byte, `\x` without hex
+/// digits and octal values above 255, are kept as written; `\\`, `\"`, `\/`
+/// and unknown escapes stand for the character itself.
+fn decode_escape(
+    rest: &str,
+    dialect: Dialect,
+    out: &mut String,
+) -> std::result::Result<usize, String> {
+    let Some(escaped) = rest.chars().next() else {
+        return Ok(0);
+    };
+    let simple = match escaped {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        'b' => '\x08',
+        'f' => '\x0C',
+        'a' => '\x07',
+        'v' => '\x0B',
+        'x' => {
+            if !dialect.gawk_extensions() {
+                return Err(dialect.extension_error("`\\x' escape"));
+            }
+            let hex: String = rest[1..]
+                .chars()
+                .take(2)
+                .take_while(char::is_ascii_hexdigit)
+                .collect();
+            match u8::from_str_radix(&hex, 16) {
+                Ok(byte) => out.push(byte as char),
+                Err(_) => out.push_str("\\x"),
+            }
+            return Ok(1 + hex.len());
+        }
+        '0'..='7' => {
+            let octal: String = rest
+                .chars()
+                .take(3)
+                .take_while(|c| ('0'..='7').contains(c))
+                .collect();
+            match u8::from_str_radix(&octal, 8) {
+                Ok(byte) => out.push(byte as char),
+                Err(_) => {
+                    out.push('\\');
+                    out.push_str(&octal);
+                }
+            }
+            return Ok(octal.len());
+        }
+        other => other,
+    };
+    out.push(simple);
+    Ok(escaped.len_utf8())
+}
+
+/// One piece of program text: a `-f` file or a `-e`/command line fragment
+pub struct SourceFragment<'s> {
+    /// File name for diagnostics, `None` for command line text
+    pub name: Option<&'s str>,
+    pub text: &'s str,
+}
+
+/// Tokenize a program made of several fragments, in order
+///
+/// The `libraries` (`-i`) are loaded first. Fragments are joined as separate
+/// lines, each keeping its own file name and line numbers, and a file
+/// included by one fragment is not included again by another.
+pub fn tokenize_program(
+    fragments: &[SourceFragment],
+    libraries: &[String],
+    dialect: Dialect,
+) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+
+    let mut lexer = Lexer::new("");
+    lexer.set_dialect(dialect);
+    for name in libraries {
+        tokens.extend(lexer.include_file(name)?);
+    }
+    let mut includes = lexer.includes;
+
+    for fragment in fragments {
+        let mut lexer = Lexer::new(fragment.text);
+        lexer.set_dialect(dialect);
+        lexer.includes = includes;
+        if let Some(name) = fragment.name {
+            lexer.set_file_name(name);
+        }
+        let mut fragment_tokens = lexer.tokenize()?;
+        // Replace the end of file by a newline between fragments
+        if let Some(eof) = fragment_tokens.pop() {
+            fragment_tokens.push(Token {
+                kind: TokenKind::Newline,
+                ..eof
+            });
+        }
+        tokens.extend(fragment_tokens);
+        includes = lexer.includes;
+        includes.end_file();
+    }
+
+    let location = tokens.last().map(|t| t.location.clone());
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        location: location.unwrap_or(SourceLocation::new(1, 1)),
+    });
+    Ok(tokens)
+}
 
 /// AWK lexer that tokenizes source code
 pub struct Lexer<'a> {
@@ -11,6 +155,14 @@ pub struct Lexer<'a> {
     line: usize,
     column: usize,
     last_token_produces_value: bool,
+    dialect: Dialect,
+    /// Name of the source file, attached to token locations
+    file: Option<Arc<str>>,
+    /// Directory `@include` searches first (the current directory if `None`)
+    dir: Option<PathBuf>,
+    includes: Includes,
+    /// Tokens of an included file, returned before lexing continues
+    pending: VecDeque<Token>,
 }
 
 impl<'a> Lexer<'a> {
@@ -21,6 +173,35 @@ impl<'a> Lexer<'a> {
             line: 1,
             column: 1,
             last_token_produces_value: false,
+            dialect: Dialect::Gawk,
+            file: None,
+            dir: None,
+            includes: Includes::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Set the dialect; gawk-only syntax is rejected outside `Dialect::Gawk`
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
+    }
+
+    /// Error for gawk-only syntax if the dialect does not allow it
+    fn check_extension(
+        &self,
+        allowed: bool,
+        extension: &str,
+        line: usize,
+        col: usize,
+    ) -> Result<()> {
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::lexer(
+                self.dialect.extension_error(extension),
+                line,
+                col,
+            ))
         }
     }
 
@@ -31,7 +212,9 @@ impl<'a> Lexer<'a> {
         let mut tokens = Vec::with_capacity(estimated_tokens.min(1024));
 
         loop {
-            let token = self.next_token()?;
+            let token = self
+                .next_token()
+                .map_err(|e| e.in_file(self.file.clone()))?;
             let is_eof = matches!(token.kind, TokenKind::Eof);
             tokens.push(token);
             if is_eof {
@@ -43,12 +226,20 @@ impl<'a> Lexer<'a> {
 
     /// Get the next token from the source
     pub fn next_token(&mut self) -> Result<Token> {
+        if let Some(token) = self.pending.pop_front() {
+            return Ok(token);
+        }
+
         self.skip_whitespace_and_comments();
 
         let (line, col) = (self.line, self.column);
 
         let Some((_pos, ch)) = self.peek_char() else {
-            return Ok(Token::new(TokenKind::Eof, line, col));
+            let token = Token::new(TokenKind::Eof, line, col);
+            return Ok(Token {
+                location: token.location.in_file(self.file.clone()),
+                ..token
+            });
         };
 
         let token = match ch {
@@ -61,6 +252,52 @@ impl<'a> Lexer<'a> {
             // String literals
             '"' => self.scan_string()?,
 
+            // Directives and typed regexp constants (gawk extensions)
+            '@' => {
+                self.advance();
+                if self.peek_char_is('/') {
+                    // Strongly typed regexp constant: @/re/
+                    self.check_extension(
+                        self.dialect.gawk_extensions(),
+                        "typed regexp constant",
+                        line,
+                        col,
+                    )?;
+                    let TokenKind::Regex(pattern) = self.scan_regex()?.kind else {
+                        unreachable!("scan_regex returns a regex token");
+                    };
+                    Token::new(TokenKind::TypedRegex(pattern), line, col)
+                } else {
+                    let word = self.scan_identifier()?;
+                    match word.kind {
+                        TokenKind::Identifier(name) if name == "include" => {
+                            self.check_extension(
+                                self.dialect.gawk_extensions(),
+                                "@include",
+                                line,
+                                col,
+                            )?;
+                            let tokens = self.include_directive(line, col)?;
+                            self.pending.extend(tokens);
+                            self.last_token_produces_value = false;
+                            return self.next_token();
+                        }
+                        TokenKind::Identifier(name) if !name.is_empty() => {
+                            self.check_extension(
+                                self.dialect.gawk_extensions(),
+                                "indirect function call",
+                                line,
+                                col,
+                            )?;
+                            Token::new(TokenKind::IndirectCall(name), line, col)
+                        }
+                        _ => {
+                            return Err(Error::lexer("unexpected character '@'", line, col));
+                        }
+                    }
+                }
+            }
+
             // Regex or division - depends on context
             '/' => {
                 if self.last_token_produces_value {
@@ -110,7 +347,17 @@ impl<'a> Lexer<'a> {
             }
             '*' => {
                 self.advance();
-                if self.peek_char_is('=') {
+                if self.peek_char_is('*') {
+                    // ** and **= are gawk (and traditional awk) spellings of ^ and ^=
+                    self.advance();
+                    self.check_extension(self.dialect != Dialect::Posix, "`**'", line, col)?;
+                    if self.peek_char_is('=') {
+                        self.advance();
+                        Token::new(TokenKind::CaretAssign, line, col)
+                    } else {
+                        Token::new(TokenKind::Caret, line, col)
+                    }
+                } else if self.peek_char_is('=') {
                     self.advance();
                     Token::new(TokenKind::StarAssign, line, col)
                 } else {
@@ -199,6 +446,10 @@ impl<'a> Lexer<'a> {
                 if self.peek_char_is('|') {
                     self.advance();
                     Token::new(TokenKind::Or, line, col)
+                } else if self.peek_char_is('&') {
+                    self.advance();
+                    self.check_extension(self.dialect.gawk_extensions(), "`|&'", line, col)?;
+                    Token::new(TokenKind::TwoWayPipe, line, col)
                 } else {
                     Token::new(TokenKind::Pipe, line, col)
                 }
@@ -258,7 +509,10 @@ impl<'a> Lexer<'a> {
         };
 
         self.last_token_produces_value = token.kind.produces_value();
-        Ok(token)
+        Ok(Token {
+            location: token.location.in_file(self.file.clone()),
+            ..token
+        })
     }
 
     fn peek_char(&mut self) -> Option<(usize, char)> {
@@ -335,78 +589,15 @@ impl<'a> Lexer<'a> {
             match self.advance() {
                 Some((_, '"')) => break,
                 Some((_, '\\')) => {
-                    // Escape sequence
-                    match self.peek_char() {
-                        Some((_, 'n')) => {
-                            self.advance();
-                            value.push('\n');
-                        }
-                        Some((_, 't')) => {
-                            self.advance();
-                            value.push('\t');
-                        }
-                        Some((_, 'r')) => {
-                            self.advance();
-                            value.push('\r');
-                        }
-                        Some((_, 'b')) => {
-                            self.advance();
-                            value.push('\x08');
-                        }
-                        Some((_, 'f')) => {
-                            self.advance();
-                            value.push('\x0C');
-                        }
-                        Some((_, 'a')) => {
-                            self.advance();
-                            value.push('\x07');
-                        }
-                        Some((_, 'v')) => {
-                            self.advance();
-                            value.push('\x0B');
-                        }
-                        Some((_, '\\')) => {
-                            self.advance();
-                            value.push('\\');
-                        }
-                        Some((_, '"')) => {
-                            self.advance();
-                            value.push('"');
-                        }
-                        Some((_, '/')) => {
-                            self.advance();
-                            value.push('/');
-                        }
-                        Some((_, 'x')) => {
-                            // Hex escape: \xNN
-                            self.advance(); // consume 'x'
-                            let hex = self.read_hex_digits(2);
-                            if let Some(ch) = u8::from_str_radix(&hex, 16).ok().map(|b| b as char) {
-                                value.push(ch);
-                            } else {
-                                value.push_str("\\x");
-                                value.push_str(&hex);
-                            }
-                        }
-                        Some((_, c)) if c.is_ascii_digit() && c != '8' && c != '9' => {
-                            // Octal escape: \NNN (1-3 octal digits)
-                            let octal = self.read_octal_digits(3);
-                            if let Some(ch) = u8::from_str_radix(&octal, 8).ok().map(|b| b as char)
-                            {
-                                value.push(ch);
-                            } else {
-                                value.push('\\');
-                                value.push_str(&octal);
-                            }
-                        }
-                        Some((_, c)) => {
-                            // Unknown escape, just use the character
-                            self.advance();
-                            value.push(c);
-                        }
-                        None => {
-                            return Err(Error::lexer("unterminated string", line, col));
-                        }
+                    let Some((pos, _)) = self.peek_char() else {
+                        return Err(Error::lexer("unterminated string", line, col));
+                    };
+                    // Errors point at the backslash
+                    let (esc_line, esc_col) = (self.line, self.column - 1);
+                    let used = decode_escape(&self.source[pos..], self.dialect, &mut value)
+                        .map_err(|message| Error::lexer(message, esc_line, esc_col))?;
+                    while matches!(self.peek_char(), Some((p, _)) if p < pos + used) {
+                        self.advance();
                     }
                 }
                 Some((_, '\n')) => {
@@ -426,34 +617,6 @@ impl<'a> Lexer<'a> {
         Ok(Token::new(TokenKind::String(value), line, col))
     }
 
-    fn read_hex_digits(&mut self, max_count: usize) -> String {
-        let mut result = String::new();
-        for _ in 0..max_count {
-            match self.peek_char() {
-                Some((_, c)) if c.is_ascii_hexdigit() => {
-                    self.advance();
-                    result.push(c);
-                }
-                _ => break,
-            }
-        }
-        result
-    }
-
-    fn read_octal_digits(&mut self, max_count: usize) -> String {
-        let mut result = String::new();
-        for _ in 0..max_count {
-            match self.peek_char() {
-                Some((_, c)) if ('0'..='7').contains(&c) => {
-                    self.advance();
-                    result.push(c);
-                }
-                _ => break,
-            }
-        }
-        result
-    }
-
     fn scan_regex(&mut self) -> Result<Token> {
         let (line, col) = (self.line, self.column);
         self.advance(); // consume opening slash
@@ -466,7 +629,16 @@ impl<'a> Lexer<'a> {
                 Some((_, '\\')) => {
                     // Escape next character in regex
                     pattern.push('\\');
+                    let (esc_line, esc_col) = (self.line, self.column - 1);
                     if let Some((_, ch)) = self.advance() {
+                        if ch == 'x' {
+                            self.check_extension(
+                                self.dialect.gawk_extensions(),
+                                "`\\x' escape",
+                                esc_line,
+                                esc_col,
+                            )?;
+                        }
                         pattern.push(ch);
                     } else {
                         return Err(Error::lexer("unterminated regex", line, col));
@@ -494,6 +666,26 @@ impl<'a> Lexer<'a> {
         let start_pos = self.chars.peek().map(|(pos, _)| *pos).unwrap_or(0);
         let mut end_pos = start_pos;
 
+        // Hexadecimal constant: 0x1F (gawk extension)
+        let rest = &self.source.as_bytes()[start_pos..];
+        if self.dialect.gawk_extensions()
+            && rest.len() > 2
+            && rest[0] == b'0'
+            && matches!(rest[1], b'x' | b'X')
+            && rest[2].is_ascii_hexdigit()
+        {
+            let digits = rest[2..]
+                .iter()
+                .take_while(|b| b.is_ascii_hexdigit())
+                .count();
+            for _ in 0..2 + digits {
+                self.advance();
+            }
+            let number_str = &self.source[start_pos..start_pos + 2 + digits];
+            let value = parse_non_decimal(number_str).unwrap_or_default();
+            return Ok(Token::new(TokenKind::Number(value), line, col));
+        }
+
         // Integer part
         while let Some((pos, ch)) = self.peek_char() {
             if ch.is_ascii_digit() {
@@ -540,6 +732,12 @@ impl<'a> Lexer<'a> {
         }
 
         let number_str = &self.source[start_pos..end_pos];
+        // gawk reads 011 as octal; 018 and 011.5 are still decimal
+        if self.dialect.gawk_extensions()
+            && let Some(value) = parse_non_decimal(number_str)
+        {
+            return Ok(Token::new(TokenKind::Number(value), line, col));
+        }
         let value: f64 = number_str
             .parse()
             .map_err(|_| Error::lexer(format!("invalid number '{}'", number_str), line, col))?;
@@ -564,8 +762,25 @@ impl<'a> Lexer<'a> {
         let ident = &self.source[start_pos..end_pos];
 
         // Check if it's a keyword
-        let kind =
-            keyword_to_token(ident).unwrap_or_else(|| TokenKind::Identifier(ident.to_string()));
+        let kind = match keyword_to_token(ident) {
+            // Outside gawk, case and default are ordinary names
+            Some(TokenKind::Case | TokenKind::Default) if !self.dialect.gawk_extensions() => {
+                TokenKind::Identifier(ident.to_string())
+            }
+            Some(kind) => kind,
+            None => TokenKind::Identifier(ident.to_string()),
+        };
+
+        match kind {
+            TokenKind::BeginFile | TokenKind::EndFile | TokenKind::Switch => {
+                self.check_extension(self.dialect.gawk_extensions(), ident, line, col)?
+            }
+            // nextfile is also in traditional Unix awk
+            TokenKind::Nextfile => {
+                self.check_extension(self.dialect != Dialect::Posix, ident, line, col)?
+            }
+            _ => {}
+        }
 
         Ok(Token::new(kind, line, col))
     }
@@ -998,6 +1213,13 @@ mod tests {
         assert!(matches!(tokens[1].kind, TokenKind::Pipe));
     }
 
+    #[test]
+    fn test_two_way_pipe() {
+        let mut lexer = Lexer::new("a |& b");
+        let tokens = lexer.tokenize().unwrap();
+        assert!(matches!(tokens[1].kind, TokenKind::TwoWayPipe));
+    }
+
     #[test]
     fn test_double_pipe() {
         let mut lexer = Lexer::new("a || b");
@@ -1030,4 +1252,128 @@ mod tests {
         assert!(matches!(tokens[7].kind, TokenKind::StarAssign));
         assert!(matches!(tokens[10].kind, TokenKind::PercentAssign));
     }
+
+    fn lex_in(dialect: Dialect, source: &str) -> Result<Vec<Token>> {
+        let mut lexer = Lexer::new(source);
+        lexer.set_dialect(dialect);
+        lexer.tokenize()
+    }
+
+    #[test]
+    fn test_non_decimal_constants() {
+        let numbers = |dialect, source| -> Vec<TokenKind> {
+            lex_in(dialect, source)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.kind)
+                .filter(|k| k != &TokenKind::Eof)
+                .collect()
+        };
+        assert_eq!(
+            numbers(Dialect::Gawk, "0x1F 0XaB 011 018 011.5 0"),
+            [31.0, 171.0, 9.0, 18.0, 11.5, 0.0].map(TokenKind::Number)
+        );
+        // Elsewhere 0x1F is 0 followed by the name x1F, and 011 is decimal
+        assert_eq!(
+            numbers(Dialect::Posix, "0x1F 011"),
+            [
+                TokenKind::Number(0.0),
+                TokenKind::Identifier("x1F".to_string()),
+                TokenKind::Number(11.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_star_power() {
+        let tokens = lex_in(Dialect::Gawk, "x ** 2; x **= 3").unwrap();
+        assert!(matches!(tokens[1].kind, TokenKind::Caret));
+        assert!(matches!(tokens[5].kind, TokenKind::CaretAssign));
+        assert!(lex_in(Dialect::Traditional, "x ** 2").is_ok());
+        let err = lex_in(Dialect::Posix, "x ** 2").unwrap_err().to_string();
+        assert!(err.contains("`**' is a gawk extension"), "{}", err);
+        assert!(err.contains("--posix"), "{}", err);
+    }
+
+    #[test]
+    fn test_dialect_rejects_gawk_syntax() {
+        for source in [
+            "BEGINFILE { }",
+            "switch (x) { }",
+            "x = @/re/",
+            "x = @f(1)",
+            "cmd |& getline",
+            r#"x = "\x41""#,
+            r"/\x41/",
+        ] {
+            assert!(lex_in(Dialect::Gawk, source).is_ok(), "{}", source);
+            for dialect in [Dialect::Posix, Dialect::Traditional] {
+                let err = lex_in(dialect, source).unwrap_err().to_string();
+                assert!(err.contains(dialect.option()), "{}", err);
+            }
+        }
+        assert!(lex_in(Dialect::Traditional, "{ nextfile }").is_ok());
+        let err = lex_in(Dialect::Posix, "{ nextfile }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("nextfile is a gawk extension"), "{}", err);
+
+        // case and default are only reserved in gawk mode
+        let tokens = lex_in(Dialect::Posix, "case = default").unwrap();
+        assert!(matches!(tokens[0].kind, TokenKind::Identifier(_)));
+        assert!(matches!(tokens[2].kind, TokenKind::Identifier(_)));
+    }
+
+    #[test]
+    fn test_tokenize_program_fragments() {
+        let fragments = [
+            SourceFragment {
+                name: Some("lib.awk"),
+                text: "function f() { return 1 }",
+            },
+            SourceFragment {
+                name: None,
+                text: "BEGIN {\n print f() }",
+            },
+        ];
+        let tokens = tokenize_program(&fragments, &[], Dialect::Gawk).unwrap();
+        assert_eq!(tokens[0].location.file.as_deref(), Some("lib.awk"));
+        let print = tokens.iter().find(|t| t.kind == TokenKind::Print).unwrap();
+        assert_eq!(print.location.file, None);
+        assert_eq!(print.location.line, 2);
+        assert!(matches!(tokens.last().unwrap().kind, TokenKind::Eof));
+        assert_eq!(
+            tokens.iter().filter(|t| t.kind == TokenKind::Eof).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        let unescape = |text| unescape(text, Dialect::Gawk).unwrap();
+        assert_eq!(unescape(r"a\tb\nc"), "a\tb\nc");
+        assert_eq!(unescape(r#"\x41\102\\\""#), "AB\\\"");
+        assert_eq!(unescape(r"\q\xz"), "q\\xz");
+        assert_eq!(unescape(r"\777\0"), "\\777\0");
+        assert_eq!(unescape("end\\"), "end\\");
+        assert_eq!(unescape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_unescape_matches_string_literals() {
+        for text in [r"\x41\xg", r"\777\101", r#"\q\/\""#, r"é\é"] {
+            let literal = format!("\"{}\"", text);
+            let tokens = Lexer::new(&literal).tokenize().unwrap();
+            assert_eq!(
+                tokens[0].kind,
+                TokenKind::String(unescape(text, Dialect::Gawk).unwrap())
+            );
+        }
+
+        let err = unescape(r"\x41", Dialect::Posix).unwrap_err();
+        assert!(err.contains("`\\x' escape"), "{}", err);
+        let mut lexer = Lexer::new(r#""\x41""#);
+        lexer.set_dialect(Dialect::Posix);
+        assert!(lexer.tokenize().is_err());
+    }
 }
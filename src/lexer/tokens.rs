@@ -7,9 +7,11 @@ pub enum TokenKind {
     Number(f64),
     String(String),
     Regex(String),
+    TypedRegex(String), // @/re/ (gawk extension)
 
     // Identifiers and keywords
     Identifier(String),
+    IndirectCall(String), // @name, calling the function named by `name` (gawk extension)
     Begin,
     End,
     BeginFile, // gawk extension
@@ -21,6 +23,9 @@ pub enum TokenKind {
     Do,
     Break,
     Continue,
+    Switch,  // gawk extension
+    Case,    // gawk extension
+    Default, // gawk extension
     Function,
     Return,
     Delete,
@@ -71,11 +76,12 @@ pub enum TokenKind {
     Decrement, // --
 
     // Special operators
-    Dollar,   // $ (field access)
-    Question, // ?
-    Colon,    // :
-    Pipe,     // |
-    Append,   // >>
+    Dollar,     // $ (field access)
+    Question,   // ?
+    Colon,      // :
+    Pipe,       // |
+    TwoWayPipe, // |& (gawk coprocess)
+    Append,     // >>
 
     // Delimiters
     LeftParen,    // (
@@ -106,6 +112,9 @@ impl TokenKind {
                 | TokenKind::Do
                 | TokenKind::Break
                 | TokenKind::Continue
+                | TokenKind::Switch
+                | TokenKind::Case
+                | TokenKind::Default
                 | TokenKind::Function
                 | TokenKind::Return
                 | TokenKind::Delete
@@ -126,7 +135,9 @@ impl TokenKind {
             TokenKind::Number(_)
                 | TokenKind::String(_)
                 | TokenKind::Regex(_)
+                | TokenKind::TypedRegex(_)
                 | TokenKind::Identifier(_)
+                | TokenKind::IndirectCall(_)
                 | TokenKind::LeftParen
                 | TokenKind::Dollar
                 | TokenKind::Not
@@ -183,6 +194,9 @@ pub fn keyword_to_token(s: &str) -> Option<TokenKind> {
         "do" => Some(TokenKind::Do),
         "break" => Some(TokenKind::Break),
         "continue" => Some(TokenKind::Continue),
+        "switch" => Some(TokenKind::Switch),
+        "case" => Some(TokenKind::Case),
+        "default" => Some(TokenKind::Default),
         "function" => Some(TokenKind::Function),
         "return" => Some(TokenKind::Return),
         "delete" => Some(TokenKind::Delete),
@@ -70,12 +70,15 @@
 //! ```
 
 pub mod ast;
+pub mod cli;
+pub mod dialect;
 pub mod error;
 pub mod interpreter;
 pub mod lexer;
 pub mod parser;
 pub mod value;
 
+pub use dialect::Dialect;
 pub use error::{Error, Result, SourceLocation};
 pub use interpreter::Interpreter;
 pub use lexer::{Lexer, Token, TokenKind};
@@ -1,14 +1,25 @@
 use std::env;
-use std::fs::{self, File};
-use std::io::{self, BufReader};
+use std::fs;
+use std::io;
 use std::process;
 
-use awk_rs::{Interpreter, Lexer, Parser};
+use awk_rs::cli::{Options, ProgramSource, parse_args};
+use awk_rs::lexer::{SourceFragment, tokenize_program};
+use awk_rs::{Dialect, Interpreter, Parser};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    match run(&args[1..]) {
+    let options = match parse_args(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("awk-rs: {}", e);
+            eprintln!("Try 'awk-rs --help' for more information.");
+            process::exit(2);
+        }
+    };
+
+    match run(options) {
         Ok(code) => process::exit(code),
         Err(e) => {
             eprintln!("awk-rs: {}", e);
@@ -17,144 +28,82 @@ fn main() {
     }
 }
 
-fn run(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
-    let mut field_separator = " ".to_string();
-    let mut program_source: Option<String> = None;
-    let mut input_files: Vec<String> = Vec::new();
-    let mut variables: Vec<(String, String)> = Vec::new();
-    let mut posix_mode = false;
-    let mut traditional_mode = false;
-
-    let mut i = 0;
-    while i < args.len() {
-        let arg = &args[i];
-
-        if arg == "--help" || arg == "-h" {
-            print_help();
-            return Ok(0);
-        }
-
-        if arg == "--version" {
-            println!("awk-rs {}", env!("CARGO_PKG_VERSION"));
-            return Ok(0);
-        }
-
-        if arg == "--posix" || arg == "-P" {
-            posix_mode = true;
-            traditional_mode = false;
-            i += 1;
-            continue;
-        }
-
-        if arg == "--traditional" || arg == "--compat" || arg == "-c" {
-            traditional_mode = true;
-            posix_mode = false;
-            i += 1;
-            continue;
-        }
+fn run(options: Options) -> Result<i32, Box<dyn std::error::Error>> {
+    if options.help {
+        print_help();
+        return Ok(0);
+    }
+    if options.version {
+        println!("awk-rs {}", env!("CARGO_PKG_VERSION"));
+        return Ok(0);
+    }
 
-        if arg == "-F" {
-            i += 1;
-            if i >= args.len() {
-                return Err("option -F requires an argument".into());
-            }
-            field_separator = args[i].clone();
-        } else if let Some(fs) = arg.strip_prefix("-F") {
-            field_separator = fs.to_string();
-        } else if arg == "-v" {
-            i += 1;
-            if i >= args.len() {
-                return Err("option -v requires an argument".into());
-            }
-            let var_assign = &args[i];
-            if let Some((name, value)) = var_assign.split_once('=') {
-                variables.push((name.to_string(), value.to_string()));
-            } else {
-                return Err(format!("invalid variable assignment: {}", var_assign).into());
-            }
-        } else if arg == "-f" {
-            i += 1;
-            if i >= args.len() {
-                return Err("option -f requires an argument".into());
+    // Program text from -f and -e, in order, with the file name for -f
+    let mut program_sources: Vec<(Option<String>, String)> = Vec::new();
+    for source in options.sources {
+        match source {
+            ProgramSource::File(name) => {
+                let text = if name == "-" {
+                    io::read_to_string(io::stdin())?
+                } else {
+                    fs::read_to_string(&name)
+                        .map_err(|e| format!("cannot open source file `{}': {}", name, e))?
+                };
+                program_sources.push((Some(name), text));
             }
-            let script_file = &args[i];
-            program_source = Some(fs::read_to_string(script_file)?);
-        } else if arg == "--" {
-            // End of options
-            i += 1;
-            input_files.extend(args[i..].iter().cloned());
-            break;
-        } else if arg.starts_with('-') && arg != "-" {
-            return Err(format!("unknown option: {}", arg).into());
-        } else if program_source.is_none() {
-            // First non-option argument is the program
-            program_source = Some(arg.clone());
-        } else {
-            // Rest are input files
-            input_files.push(arg.clone());
+            ProgramSource::Text(text) => program_sources.push((None, text)),
         }
-
-        i += 1;
     }
 
-    let program_source = program_source.ok_or("no program provided")?;
+    let dialect = options.dialect;
 
     // Parse the program
-    let mut lexer = Lexer::new(&program_source);
-    let tokens = lexer.tokenize()?;
+    let fragments: Vec<SourceFragment> = program_sources
+        .iter()
+        .map(|(name, text)| SourceFragment {
+            name: name.as_deref(),
+            text,
+        })
+        .collect();
+    let tokens = tokenize_program(&fragments, &options.includes, dialect)?;
     let mut parser = Parser::new(tokens);
+    parser.set_dialect(dialect);
     let program = parser.parse()?;
 
     // Create interpreter
     let mut interpreter = Interpreter::new(&program);
 
     // Set mode flags
-    interpreter.set_posix_mode(posix_mode);
-    interpreter.set_traditional_mode(traditional_mode);
+    interpreter.set_posix_mode(dialect == Dialect::Posix);
+    interpreter.set_traditional_mode(dialect == Dialect::Traditional);
+    interpreter.set_non_decimal_data(options.non_decimal_data);
 
     // Set field separator
-    interpreter.set_fs(&field_separator);
+    if let Some(fs) = &options.field_separator {
+        interpreter.set_fs(fs);
+    }
 
-    // Set ARGC and ARGV (ARGV[0] is "awk", ARGV[1...] are input files)
+    // Set ARGC and ARGV (ARGV[0] is "awk", ARGV[1...] are the operands)
     let mut argv = vec!["awk".to_string()];
-    argv.extend(input_files.iter().cloned());
+    argv.extend(options.operands);
     interpreter.set_args(argv);
 
     // Set variables
-    for (name, value) in &variables {
-        interpreter.set_variable(name, value);
+    for (name, value) in &options.assignments {
+        interpreter.set_variable(name, value)?;
     }
 
     // Prepare output
     let stdout = io::stdout();
     let mut output = stdout.lock();
 
-    // Prepare inputs
-    let exit_code = if input_files.is_empty() {
-        // Read from stdin
-        interpreter.set_filename("");
-        let stdin = io::stdin();
-        let inputs = vec![BufReader::new(stdin.lock())];
-        interpreter.run(inputs, &mut output)?
-    } else {
-        // Read from files
-        let mut exit_code = 0;
-        for filename in &input_files {
-            interpreter.set_filename(filename);
-            if filename == "-" {
-                let stdin = io::stdin();
-                let inputs = vec![BufReader::new(stdin.lock())];
-                exit_code = interpreter.run(inputs, &mut output)?;
-            } else {
-                let file = File::open(filename)?;
-                let inputs = vec![BufReader::new(file)];
-                exit_code = interpreter.run(inputs, &mut output)?;
-            }
-        }
-        exit_code
-    };
+    // BEGIN and END run once; input files are opened as ARGV is processed
+    let result = interpreter.run_argv(&mut output);
+    for warning in interpreter.warnings() {
+        eprintln!("awk-rs: warning: {}", warning);
+    }
 
-    Ok(exit_code)
+    Ok(result?)
 }
 
 fn print_help() {
@@ -165,20 +114,31 @@ fn print_help() {
 A 100% POSIX-compatible AWK implementation in Rust with gawk extensions.
 
 Options:
-  -F fs            Set the field separator to fs
-  -v var=val       Assign value to variable before execution
-  -f progfile      Read the AWK program from file
+  -F, --field-separator fs  Set the field separator to fs (-Ft is a tab)
+  -v, --assign var=val  Assign value to variable before execution
+  -f, --file progfile  Read the AWK program from file (repeatable, - for stdin)
+  -e, --source text  Use text as AWK program source, combined in order with -f
+  -i, --include file  Load an AWK library, as with @include
   -P, --posix      Strict POSIX mode (disable gawk extensions)
   -c, --traditional Traditional AWK mode (disable gawk extensions)
+  -n, --non-decimal-data  Treat hex (0x1F) and octal (017) input data as numbers
   --version        Print version information
   --help           Print this help message
 
 GAWK Extensions (disabled with --posix or --traditional):
-  FPAT             Field pattern for content-based splitting
-  FIELDWIDTHS      Fixed-width field splitting
+  FPAT, FIELDWIDTHS, IGNORECASE, PROCINFO, RT  Special variables
   BEGINFILE/ENDFILE Patterns for file processing
+  switch/case       Multi-way branch statement
+  a[i][j]           Arrays of arrays
+  @f(args)          Call the function named by variable f
   systime(), mktime(), strftime() Time functions
   gensub(), patsplit(), asort(), asorti() String/array functions
+  typeof(), isarray(), @/re/ Type introspection and typed regexps
+  and(), or(), xor(), compl(), lshift(), rshift() Bit manipulation
+  intdiv(), strtonum() Integer division and hex/octal conversion
+  |&, \x escapes   Coprocesses and hex escapes
+  \y \< \> \s \w    GNU regex operators
+  **, **=, nextfile Also accepted by --traditional
 
 Examples:
   awk-rs '{{ print $1 }}' file.txt
@@ -1,4 +1,5 @@
 use crate::ast::*;
+use crate::dialect::{Dialect, GAWK_FUNCTIONS};
 use crate::error::{Error, Result, SourceLocation};
 use crate::lexer::{Token, TokenKind};
 
@@ -6,11 +7,25 @@ use crate::lexer::{Token, TokenKind};
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    dialect: Dialect,
+    /// Calls to gawk-only builtins, checked once all functions are known
+    gawk_calls: Vec<(String, SourceLocation)>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            dialect: Dialect::Gawk,
+            gawk_calls: Vec::new(),
+        }
+    }
+
+    /// Set the dialect; calls to gawk-only builtins are rejected outside
+    /// `Dialect::Gawk` unless the program defines a function of that name
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = dialect;
     }
 
     /// Parse a complete AWK program
@@ -29,6 +44,17 @@ impl Parser {
             self.skip_newlines();
         }
 
+        if let Some((name, location)) = self
+            .gawk_calls
+            .iter()
+            .find(|(name, _)| !program.functions.iter().any(|f| &f.name == name))
+        {
+            return Err(Error::parser_in(
+                self.dialect.extension_error(&format!("{}()", name)),
+                location.clone(),
+            ));
+        }
+
         Ok(program)
     }
 
@@ -228,6 +254,11 @@ impl Parser {
             return self.parse_do_while_statement(location);
         }
 
+        // Switch statement
+        if self.match_token(&TokenKind::Switch) {
+            return self.parse_switch_statement(location);
+        }
+
         // Break
         if self.match_token(&TokenKind::Break) {
             return Ok(Stmt::Break { location });
@@ -272,21 +303,13 @@ impl Parser {
         if self.match_token(&TokenKind::Delete) {
             let name = self.expect_identifier()?;
 
-            // Check if there's an index (delete array[i]) or not (delete array)
-            let indices = if self.match_token(&TokenKind::LeftBracket) {
-                let mut indices = vec![self.parse_expression()?];
-                while self.match_token(&TokenKind::Comma) {
-                    indices.push(self.parse_expression()?);
-                }
-                self.expect(&TokenKind::RightBracket)?;
-                indices
-            } else {
-                // delete array (entire array)
-                Vec::new()
-            };
+            // delete array[i] or, with no index, the entire array
+            let mut path = self.parse_subscripts()?;
+            let indices = path.pop().unwrap_or_default();
 
             return Ok(Stmt::Delete {
                 array: name,
+                path,
                 index: indices,
                 location,
             });
@@ -332,6 +355,92 @@ impl Parser {
         })
     }
 
+    fn parse_switch_statement(&mut self, location: SourceLocation) -> Result<Stmt> {
+        self.expect(&TokenKind::LeftParen)?;
+        let subject = self.parse_expression()?;
+        self.expect(&TokenKind::RightParen)?;
+        self.skip_newlines();
+        self.expect(&TokenKind::LeftBrace)?;
+        self.skip_terminators();
+
+        let mut cases: Vec<SwitchCase> = Vec::new();
+        while !self.check(&TokenKind::RightBrace) && !self.is_at_end() {
+            let case_location = self.current_location();
+            let label = if self.match_token(&TokenKind::Default) {
+                if cases.iter().any(|c| c.label == CaseLabel::Default) {
+                    return Err(Error::parser_in(
+                        "more than one default in switch body",
+                        case_location,
+                    ));
+                }
+                CaseLabel::Default
+            } else if self.match_token(&TokenKind::Case) {
+                let label = self.parse_case_label()?;
+                if cases.iter().any(|c| c.label == label) {
+                    return Err(Error::parser_in(
+                        format!("duplicate case values in switch body: {}", label),
+                        case_location,
+                    ));
+                }
+                label
+            } else {
+                return Err(Error::parser_in(
+                    format!("expected case or default, found {:?}", self.peek_kind()),
+                    case_location,
+                ));
+            };
+            self.expect(&TokenKind::Colon)?;
+            self.skip_terminators();
+
+            let mut body = Vec::new();
+            while !matches!(
+                self.peek_kind(),
+                Some(TokenKind::Case | TokenKind::Default | TokenKind::RightBrace) | None
+            ) && !self.is_at_end()
+            {
+                body.push(self.parse_statement()?);
+                self.skip_terminators();
+            }
+
+            cases.push(SwitchCase {
+                label,
+                body,
+                location: case_location,
+            });
+        }
+        self.expect(&TokenKind::RightBrace)?;
+
+        Ok(Stmt::Switch {
+            subject,
+            cases,
+            location,
+        })
+    }
+
+    /// Parse the constant after `case`: a number, string or regex literal
+    fn parse_case_label(&mut self) -> Result<CaseLabel> {
+        let location = self.current_location();
+        let negative = if self.match_token(&TokenKind::Minus) {
+            true
+        } else {
+            self.match_token(&TokenKind::Plus);
+            false
+        };
+        let label = match self.peek_kind() {
+            Some(TokenKind::Number(n)) => CaseLabel::Number(if negative { -n } else { *n }),
+            Some(TokenKind::String(s)) if !negative => CaseLabel::String(s.clone()),
+            Some(TokenKind::Regex(r)) if !negative => CaseLabel::Regex(r.clone()),
+            _ => {
+                return Err(Error::parser_in(
+                    "case value must be a number, string or regex constant",
+                    location,
+                ));
+            }
+        };
+        self.advance();
+        Ok(label)
+    }
+
     fn parse_while_statement(&mut self, location: SourceLocation) -> Result<Stmt> {
         self.expect(&TokenKind::LeftParen)?;
         let condition = self.parse_expression()?;
@@ -358,6 +467,7 @@ impl Parser {
 
             if self.match_token(&TokenKind::In) {
                 let array = self.expect_identifier()?;
+                let path = self.parse_subscripts()?;
                 self.expect(&TokenKind::RightParen)?;
                 self.skip_newlines();
                 let body = Box::new(self.parse_statement()?);
@@ -365,6 +475,7 @@ impl Parser {
                 return Ok(Stmt::ForIn {
                     var: name,
                     array,
+                    path,
                     body,
                     location,
                 });
@@ -432,6 +543,7 @@ impl Parser {
             && !self.check(&TokenKind::Greater)
             && !self.check(&TokenKind::Append)
             && !self.check(&TokenKind::Pipe)
+            && !self.check(&TokenKind::TwoWayPipe)
         {
             args.push(self.parse_print_arg()?);
             while self.match_token(&TokenKind::Comma) {
@@ -532,9 +644,11 @@ impl Parser {
         if self.match_token(&TokenKind::In) {
             let location = self.current_location();
             let array = self.expect_identifier()?;
+            let path = self.parse_subscripts()?;
             return Ok(Expr::InArray {
                 key: vec![expr],
                 array,
+                path,
                 location,
             });
         }
@@ -613,6 +727,9 @@ impl Parser {
         } else if self.match_token(&TokenKind::Pipe) {
             let target = self.parse_print_arg()?;
             Ok(Some(OutputRedirect::Pipe(target)))
+        } else if self.match_token(&TokenKind::TwoWayPipe) {
+            let target = self.parse_print_arg()?;
+            Ok(Some(OutputRedirect::Coprocess(target)))
         } else {
             Ok(None)
         }
@@ -720,9 +837,11 @@ impl Parser {
         if self.match_token(&TokenKind::In) {
             let location = self.current_location();
             let array = self.expect_identifier()?;
+            let path = self.parse_subscripts()?;
             return Ok(Expr::InArray {
                 key: vec![expr],
                 array,
+                path,
                 location,
             });
         }
@@ -730,12 +849,13 @@ impl Parser {
         Ok(expr)
     }
 
-    /// Handle `cmd | getline [var]` syntax
+    /// Handle `cmd | getline [var]` and `cmd |& getline [var]` syntax
     fn parse_pipe_getline(&mut self) -> Result<Expr> {
         let expr = self.parse_match()?;
 
         // Check for pipe to getline: expr | getline [var]
-        if self.check(&TokenKind::Pipe) {
+        if self.check(&TokenKind::Pipe) || self.check(&TokenKind::TwoWayPipe) {
+            let two_way = self.check(&TokenKind::TwoWayPipe);
             // Look ahead to see if getline follows
             let saved_pos = self.current;
             self.advance(); // consume |
@@ -753,9 +873,14 @@ impl Parser {
                     None
                 };
 
+                let input = if two_way {
+                    GetlineInput::Coprocess(Box::new(expr))
+                } else {
+                    GetlineInput::Pipe(Box::new(expr))
+                };
                 return Ok(Expr::Getline {
                     var,
-                    input: Some(GetlineInput::Pipe(Box::new(expr))),
+                    input: Some(input),
                     location,
                 });
             } else {
@@ -852,7 +977,9 @@ impl Parser {
                 kind,
                 TokenKind::Number(_)
                     | TokenKind::String(_)
+                    | TokenKind::TypedRegex(_)
                     | TokenKind::Identifier(_)
+                    | TokenKind::IndirectCall(_)
                     | TokenKind::Dollar
                     | TokenKind::LeftParen
                     | TokenKind::Not
@@ -987,24 +1114,21 @@ impl Parser {
                 expr = Expr::PostIncrement(Box::new(expr), location);
             } else if self.match_token(&TokenKind::Decrement) {
                 expr = Expr::PostDecrement(Box::new(expr), location);
-            } else if self.match_token(&TokenKind::LeftBracket) {
-                // Array access
+            } else if self.check(&TokenKind::LeftBracket) {
+                // Array access, possibly into subarrays: a[i][j]
                 if let Expr::Var(name, _) = expr {
-                    let mut indices = vec![self.parse_expression()?];
-                    while self.match_token(&TokenKind::Comma) {
-                        indices.push(self.parse_expression()?);
-                    }
-                    self.expect(&TokenKind::RightBracket)?;
+                    let mut path = self.parse_subscripts()?;
+                    let indices = path.pop().unwrap_or_default();
                     expr = Expr::ArrayAccess {
                         array: name,
+                        path,
                         indices,
                         location,
                     };
                 } else {
-                    return Err(Error::parser(
+                    return Err(Error::parser_in(
                         "array access requires variable name",
-                        location.line,
-                        location.column,
+                        location,
                     ));
                 }
             } else {
@@ -1015,6 +1139,28 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Parse the `[...]` subscripts after an array name, one list per pair of
+    /// brackets; more than one selects into subarrays (gawk extension)
+    fn parse_subscripts(&mut self) -> Result<Vec<Vec<Expr>>> {
+        let mut subscripts = Vec::new();
+        while self.check(&TokenKind::LeftBracket) {
+            if !subscripts.is_empty() && !self.dialect.gawk_extensions() {
+                return Err(Error::parser_in(
+                    self.dialect.extension_error("arrays of arrays"),
+                    self.current_location(),
+                ));
+            }
+            self.advance();
+            let mut indices = vec![self.parse_expression()?];
+            while self.match_token(&TokenKind::Comma) {
+                indices.push(self.parse_expression()?);
+            }
+            self.expect(&TokenKind::RightBracket)?;
+            subscripts.push(indices);
+        }
+        Ok(subscripts)
+    }
+
     fn parse_field(&mut self) -> Result<Expr> {
         if self.match_token(&TokenKind::Dollar) {
             let location = self.current_location();
@@ -1049,6 +1195,13 @@ impl Parser {
             return Ok(Expr::Regex(r, location));
         }
 
+        // Typed regexp constant
+        if let Some(TokenKind::TypedRegex(r)) = self.peek_kind() {
+            let r = r.clone();
+            self.advance();
+            return Ok(Expr::TypedRegex(r, location));
+        }
+
         // Identifier (variable or function call)
         if let Some(TokenKind::Identifier(name)) = self.peek_kind() {
             let name = name.clone();
@@ -1056,14 +1209,10 @@ impl Parser {
 
             // Check for function call
             if self.match_token(&TokenKind::LeftParen) {
-                let mut args = Vec::new();
-                if !self.check(&TokenKind::RightParen) {
-                    args.push(self.parse_expression()?);
-                    while self.match_token(&TokenKind::Comma) {
-                        args.push(self.parse_expression()?);
-                    }
+                let args = self.parse_call_arguments()?;
+                if !self.dialect.gawk_extensions() && GAWK_FUNCTIONS.contains(&name.as_str()) {
+                    self.gawk_calls.push((name.clone(), location.clone()));
                 }
-                self.expect(&TokenKind::RightParen)?;
                 return Ok(Expr::Call {
                     name,
                     args,
@@ -1074,6 +1223,19 @@ impl Parser {
             return Ok(Expr::Var(name, location));
         }
 
+        // Indirect function call: @var(args)
+        if let Some(TokenKind::IndirectCall(var)) = self.peek_kind() {
+            let var = var.clone();
+            self.advance();
+            self.expect(&TokenKind::LeftParen)?;
+            let args = self.parse_call_arguments()?;
+            return Ok(Expr::IndirectCall {
+                var,
+                args,
+                location,
+            });
+        }
+
         // Getline
         if self.match_token(&TokenKind::Getline) {
             let var = if let Some(TokenKind::Identifier(name)) = self.peek_kind() {
@@ -1104,13 +1266,25 @@ impl Parser {
             return Ok(Expr::Group(Box::new(expr), location));
         }
 
-        Err(Error::parser(
+        Err(Error::parser_in(
             format!("unexpected token {:?}", self.peek_kind()),
-            location.line,
-            location.column,
+            location,
         ))
     }
 
+    /// Parse the arguments of a call, after the opening parenthesis
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if !self.check(&TokenKind::RightParen) {
+            args.push(self.parse_expression()?);
+            while self.match_token(&TokenKind::Comma) {
+                args.push(self.parse_expression()?);
+            }
+        }
+        self.expect(&TokenKind::RightParen)?;
+        Ok(args)
+    }
+
     // ===== Helper methods =====
 
     fn peek_kind(&self) -> Option<&TokenKind> {
@@ -1120,7 +1294,7 @@ impl Parser {
     fn current_location(&self) -> SourceLocation {
         self.tokens
             .get(self.current)
-            .map(|t| t.location)
+            .map(|t| t.location.clone())
             .unwrap_or(SourceLocation::new(0, 0))
     }
 
@@ -1155,10 +1329,9 @@ impl Parser {
             Ok(self.advance().unwrap())
         } else {
             let loc = self.current_location();
-            Err(Error::parser(
+            Err(Error::parser_in(
                 format!("expected {:?}, found {:?}", kind, self.peek_kind()),
-                loc.line,
-                loc.column,
+                loc,
             ))
         }
     }
@@ -1170,10 +1343,9 @@ impl Parser {
             Ok(name)
         } else {
             let loc = self.current_location();
-            Err(Error::parser(
+            Err(Error::parser_in(
                 format!("expected identifier, found {:?}", self.peek_kind()),
-                loc.line,
-                loc.column,
+                loc,
             ))
         }
     }
@@ -1327,6 +1499,36 @@ mod tests {
         assert_eq!(program.rules.len(), 1);
     }
 
+    #[test]
+    fn test_coprocess_getline() {
+        let program = parse(r#"{ "cmd" |& getline x }"#).unwrap();
+        let Some(action) = &program.rules[0].action else {
+            panic!("expected action");
+        };
+        assert!(matches!(
+            &action.statements[0],
+            Stmt::Expr(Expr::Getline {
+                input: Some(GetlineInput::Coprocess(_)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_output_coprocess() {
+        let program = parse(r#"{ print "x" |& "cmd" }"#).unwrap();
+        let Some(action) = &program.rules[0].action else {
+            panic!("expected action");
+        };
+        assert!(matches!(
+            &action.statements[0],
+            Stmt::Print {
+                output: Some(OutputRedirect::Coprocess(_)),
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_output_redirect() {
         let program = parse(r#"{ print "x" > "file" }"#).unwrap();
@@ -1483,4 +1685,120 @@ mod tests {
         let program = parse(r#"{ x = 2^3^4 }"#).unwrap(); // right associative
         assert_eq!(program.rules.len(), 1);
     }
+
+    fn parse_in(dialect: Dialect, source: &str) -> Result<Program> {
+        let mut lexer = Lexer::new(source);
+        lexer.set_dialect(dialect);
+        let tokens = lexer.tokenize()?;
+        let mut parser = Parser::new(tokens);
+        parser.set_dialect(dialect);
+        parser.parse()
+    }
+
+    #[test]
+    fn test_dialect_rejects_gawk_builtins() {
+        let source = r#"BEGIN { print gensub("a", "b", "g", "aa") }"#;
+        assert!(parse_in(Dialect::Gawk, source).is_ok());
+        let err = parse_in(Dialect::Posix, source).unwrap_err().to_string();
+        assert!(
+            err.contains("gensub() is a gawk extension and is not allowed in --posix mode"),
+            "{}",
+            err
+        );
+        assert!(err.contains("line 1, column 15"), "{}", err);
+
+        let err = parse_in(Dialect::Posix, "BEGIN { x = and(1, 3) }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("and() is a gawk extension"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_chained_subscripts() {
+        let program = parse_in(
+            Dialect::Gawk,
+            "BEGIN { a[1][2, 3] = 1; delete a[1][2]; for (k in a[1]) x = (k in a[1]) }",
+        )
+        .unwrap();
+        let statements = &program.rules[0].action.as_ref().unwrap().statements;
+        let Stmt::Expr(Expr::Assign { target, .. }) = &statements[0] else {
+            panic!("expected assignment");
+        };
+        let Expr::ArrayAccess { path, indices, .. } = target.as_ref() else {
+            panic!("expected array access");
+        };
+        assert_eq!((path.len(), path[0].len(), indices.len()), (1, 1, 2));
+        assert!(
+            matches!(&statements[1], Stmt::Delete { path, index, .. } if path.len() == 1 && index.len() == 1)
+        );
+        assert!(matches!(&statements[2], Stmt::ForIn { path, .. } if path.len() == 1));
+
+        let err = parse_in(Dialect::Posix, "BEGIN { a[1][2] = 1 }")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("arrays of arrays is a gawk extension"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_switch() {
+        let program = parse_in(
+            Dialect::Gawk,
+            "BEGIN { switch (x) { case -1: case \"a\": y\n case /b/: break\n default: } }",
+        )
+        .unwrap();
+        let Stmt::Switch { cases, .. } = &program.rules[0].action.as_ref().unwrap().statements[0]
+        else {
+            panic!("expected switch");
+        };
+        let labels: Vec<_> = cases.iter().map(|c| c.label.clone()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                CaseLabel::Number(-1.0),
+                CaseLabel::String("a".to_string()),
+                CaseLabel::Regex("b".to_string()),
+                CaseLabel::Default
+            ]
+        );
+        assert!(cases[0].body.is_empty());
+        assert_eq!(cases[1].body.len(), 1);
+
+        let err = parse_in(Dialect::Gawk, "BEGIN { switch (x) { case 1: case 1.0: } }")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("duplicate case values in switch body: 1"),
+            "{}",
+            err
+        );
+        assert!(parse_in(Dialect::Gawk, "BEGIN { switch (x) { case y: } }").is_err());
+    }
+
+    #[test]
+    fn test_parse_indirect_call() {
+        let program = parse_in(Dialect::Gawk, "BEGIN { x = 1 + @f(a, 2) }").unwrap();
+        let Stmt::Expr(Expr::Assign { value, .. }) =
+            &program.rules[0].action.as_ref().unwrap().statements[0]
+        else {
+            panic!("expected assignment");
+        };
+        let Expr::Binary { right, .. } = value.as_ref() else {
+            panic!("expected addition");
+        };
+        assert!(matches!(
+            right.as_ref(),
+            Expr::IndirectCall { var, args, .. } if var == "f" && args.len() == 2
+        ));
+        assert!(parse_in(Dialect::Gawk, "BEGIN { @f }").is_err());
+    }
+
+    #[test]
+    fn test_dialect_allows_user_functions_with_gawk_names() {
+        let source = r#"BEGIN { print systime() } function systime() { return 1 }"#;
+        assert!(parse_in(Dialect::Traditional, source).is_ok());
+    }
 }
@@ -23,8 +23,11 @@ use std::fmt;
 /// assert_eq!(s.to_string_val(), "hello");
 /// assert_eq!(s.to_number(), 0.0);  // Non-numeric string coerces to 0
 ///
-/// // Numeric strings
-/// let ns = Value::from_string("123".to_string());
+/// // String constants stay strings, even if they look numeric
+/// assert!(!Value::from_string("123".to_string()).is_numeric_string());
+///
+/// // Numeric strings come from input (fields, getline, split, ...)
+/// let ns = Value::from_input("123".to_string());
 /// assert_eq!(ns.to_number(), 123.0);
 /// assert_eq!(ns.to_string_val(), "123");
 ///
@@ -46,13 +49,35 @@ pub enum Value {
     /// Numeric string - a string that looks like a number
     /// (used for comparison semantics)
     NumericString(String, f64),
+    /// Typed regexp constant `@/re/` (gawk extension), which otherwise
+    /// behaves as a string holding the regexp text
+    Regex(String),
 }
 
 impl Value {
-    /// Create a new string value, detecting if it's a numeric string
+    /// Create a string value
+    ///
+    /// Used for string constants and the results of string operations, which
+    /// always compare as strings.
     #[inline]
     pub fn from_string(s: String) -> Self {
-        if let Some(num) = parse_numeric_string(&s) {
+        Value::String(s)
+    }
+
+    /// Create a value from input data, detecting if it's a numeric string
+    ///
+    /// Fields, getline results, split() elements, ARGV/ENVIRON entries and
+    /// `-v` assignments are numeric strings when they look like numbers.
+    #[inline]
+    pub fn from_input(s: String) -> Self {
+        Self::from_input_data(s, false)
+    }
+
+    /// Create a value from input data, also treating hex (`0x1F`) and octal
+    /// (`017`) text as numbers when `non_decimal` is set (`--non-decimal-data`)
+    #[inline]
+    pub fn from_input_data(s: String, non_decimal: bool) -> Self {
+        if let Some(num) = parse_numeric_string(&s, non_decimal) {
             Value::NumericString(s, num)
         } else {
             Value::String(s)
@@ -75,18 +100,29 @@ impl Value {
         match self {
             Value::Uninitialized => false,
             Value::Number(n) => *n != 0.0,
-            Value::String(s) => !s.is_empty(),
+            Value::String(s) | Value::Regex(s) => !s.is_empty(),
             Value::NumericString(s, _) => !s.is_empty(),
         }
     }
 
+    /// The name gawk's `typeof()` gives this kind of value
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Uninitialized => "unassigned",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::NumericString(..) => "strnum",
+            Value::Regex(_) => "regexp",
+        }
+    }
+
     /// Coerce to numeric value
     #[inline]
     pub fn to_number(&self) -> f64 {
         match self {
             Value::Uninitialized => 0.0,
             Value::Number(n) => *n,
-            Value::String(s) => parse_leading_number(s),
+            Value::String(s) | Value::Regex(s) => parse_leading_number(s),
             Value::NumericString(_, n) => *n,
         }
     }
@@ -103,7 +139,7 @@ impl Value {
         match self {
             Value::Uninitialized => Cow::Borrowed(""),
             Value::Number(n) => Cow::Owned(format_number(*n, "%.6g")),
-            Value::String(s) => Cow::Borrowed(s),
+            Value::String(s) | Value::Regex(s) => Cow::Borrowed(s),
             Value::NumericString(s, _) => Cow::Borrowed(s),
         }
     }
@@ -113,7 +149,7 @@ impl Value {
         match self {
             Value::Uninitialized => String::new(),
             Value::Number(n) => format_number(*n, format),
-            Value::String(s) => s.clone(),
+            Value::String(s) | Value::Regex(s) => s.clone(),
             Value::NumericString(s, _) => s.clone(),
         }
     }
@@ -145,7 +181,7 @@ impl fmt::Display for Value {
         match self {
             Value::Uninitialized => Ok(()),
             Value::Number(n) => write!(f, "{}", format_number(*n, "%.6g")),
-            Value::String(s) => write!(f, "{}", s),
+            Value::String(s) | Value::Regex(s) => write!(f, "{}", s),
             Value::NumericString(s, _) => write!(f, "{}", s),
         }
     }
@@ -154,11 +190,22 @@ impl fmt::Display for Value {
 /// Compare two AWK values according to AWK comparison rules
 #[inline]
 pub fn compare_values(left: &Value, right: &Value) -> Ordering {
+    compare_values_folding(left, right, false)
+}
+
+/// Compare two AWK values, ignoring case in string comparisons when
+/// `ignore_case` is set (gawk's IGNORECASE)
+#[inline]
+pub fn compare_values_folding(left: &Value, right: &Value, ignore_case: bool) -> Ordering {
     // If both are numeric or numeric strings, compare numerically
     if left.compares_as_number() && right.compares_as_number() {
         let l = left.to_number();
         let r = right.to_number();
         l.partial_cmp(&r).unwrap_or(Ordering::Equal)
+    } else if ignore_case {
+        left.as_str()
+            .to_lowercase()
+            .cmp(&right.as_str().to_lowercase())
     } else {
         // Otherwise compare as strings - use as_str to avoid allocation
         left.as_str().cmp(&right.as_str())
@@ -241,14 +288,65 @@ pub fn parse_leading_number(s: &str) -> f64 {
     num_str.parse().unwrap_or(0.0)
 }
 
+/// Parse a leading hexadecimal (`0x1F`) or octal (`017`) integer, as gawk's
+/// `strtonum` does
+///
+/// Returns `None` when the string is decimal: no leading zero, `0x` without
+/// hex digits, or octal-looking digits that include 8 or 9 or are followed by
+/// a decimal point or exponent.
+pub fn parse_non_decimal(s: &str) -> Option<f64> {
+    let s = s.trim_start().as_bytes();
+    if s.first() != Some(&b'0') {
+        return None;
+    }
+
+    if matches!(s.get(1), Some(b'x' | b'X')) {
+        let digits: Vec<u32> = s[2..]
+            .iter()
+            .map_while(|&b| (b as char).to_digit(16))
+            .collect();
+        if digits.is_empty() {
+            return None;
+        }
+        return Some(digits.iter().fold(0.0, |n, &d| n * 16.0 + d as f64));
+    }
+
+    let digits = s[1..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let octal = &s[1..1 + digits];
+    if digits == 0
+        || octal.iter().any(|&b| b > b'7')
+        || matches!(s.get(1 + digits), Some(b'.' | b'e' | b'E'))
+    {
+        return None;
+    }
+    Some(octal.iter().fold(0.0, |n, &b| n * 8.0 + (b - b'0') as f64))
+}
+
 /// Check if a string is a numeric string (looks entirely like a number)
+///
+/// With `non_decimal`, an entire hex or octal integer such as `0x1F` or
+/// `017` counts too.
 #[inline]
-fn parse_numeric_string(s: &str) -> Option<f64> {
+fn parse_numeric_string(s: &str, non_decimal: bool) -> Option<f64> {
     let trimmed = s.trim();
     if trimmed.is_empty() {
         return None;
     }
 
+    if non_decimal && let Some(n) = parse_non_decimal(trimmed) {
+        // parse_non_decimal ignores trailing text, which must not be there
+        let whole = match trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+        {
+            Some(hex) => hex.bytes().all(|b| b.is_ascii_hexdigit()),
+            None => trimmed.bytes().all(|b| b.is_ascii_digit()),
+        };
+        if whole {
+            return Some(n);
+        }
+    }
+
     // Fast path: check if it's a simple integer
     if trimmed.bytes().all(|b| b.is_ascii_digit()) {
         return trimmed.parse().ok();
@@ -380,7 +478,7 @@ mod tests {
 
     #[test]
     fn test_numeric_string() {
-        let v = Value::from_string("42".to_string());
+        let v = Value::from_input("42".to_string());
         assert!(v.is_numeric_string());
         assert_eq!(v.to_number(), 42.0);
         assert_eq!(v.to_string_val(), "42");
@@ -395,14 +493,34 @@ mod tests {
         assert_eq!(parse_leading_number("1e10"), 1e10);
     }
 
+    #[test]
+    fn test_non_decimal() {
+        assert_eq!(parse_non_decimal("0x1F"), Some(31.0));
+        assert_eq!(parse_non_decimal("  0XffG"), Some(255.0));
+        assert_eq!(parse_non_decimal("017"), Some(15.0));
+        assert_eq!(parse_non_decimal("0x"), None);
+        assert_eq!(parse_non_decimal("018"), None);
+        assert_eq!(parse_non_decimal("010.5"), None);
+        assert_eq!(parse_non_decimal("0"), None);
+        assert_eq!(parse_non_decimal("17"), None);
+
+        assert_eq!(parse_numeric_string(" 0x1A ", true), Some(26.0));
+        assert_eq!(parse_numeric_string("011", true), Some(9.0));
+        assert_eq!(parse_numeric_string("011", false), Some(11.0));
+        assert_eq!(parse_numeric_string("018", true), Some(18.0));
+        assert_eq!(parse_numeric_string("0x1G", true), None);
+        assert_eq!(parse_numeric_string("017abc", true), None);
+        assert_eq!(parse_numeric_string("0x1A", false), None);
+    }
+
     #[test]
     fn test_comparison() {
         let n1 = Value::Number(10.0);
         let n2 = Value::Number(2.0);
         assert_eq!(compare_values(&n1, &n2), Ordering::Greater);
 
-        let s1 = Value::from_string("10".to_string());
-        let s2 = Value::from_string("2".to_string());
+        let s1 = Value::from_input("10".to_string());
+        let s2 = Value::from_input("2".to_string());
         // Both numeric strings -> compare numerically
         assert_eq!(compare_values(&s1, &s2), Ordering::Greater);
 
@@ -412,6 +530,68 @@ mod tests {
         assert_eq!(compare_values(&s3, &s4), Ordering::Less);
     }
 
+    #[test]
+    fn test_constant_strings_are_not_numeric() {
+        let v = Value::from_string("42".to_string());
+        assert!(!v.is_numeric_string());
+        assert_eq!(v.to_number(), 42.0);
+
+        // "10" < "9" as strings, but 10 > 9 as input-derived numeric strings
+        let (c10, c9) = (
+            Value::from_string("10".to_string()),
+            Value::from_string("9".to_string()),
+        );
+        assert_eq!(compare_values(&c10, &c9), Ordering::Less);
+        let (i10, i9) = (
+            Value::from_input("10".to_string()),
+            Value::from_input("9".to_string()),
+        );
+        assert_eq!(compare_values(&i10, &i9), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(Value::Uninitialized.type_name(), "unassigned");
+        assert_eq!(Value::Number(1.0).type_name(), "number");
+        assert_eq!(Value::from_string("1".to_string()).type_name(), "string");
+        assert_eq!(Value::from_input("1".to_string()).type_name(), "strnum");
+        assert_eq!(Value::from_input("x".to_string()).type_name(), "string");
+        let regex = Value::Regex("a+".to_string());
+        assert_eq!(regex.type_name(), "regexp");
+        assert_eq!(regex.to_string_val(), "a+");
+        assert!(!regex.compares_as_number());
+    }
+
+    #[test]
+    fn test_comparison_matrix() {
+        let num = Value::Number(10.0);
+        let strnum = Value::from_input(" 10 ".to_string());
+        let string = Value::from_string("10".to_string());
+        let text = Value::from_input("abc".to_string());
+
+        // Number and strnum compare numerically
+        assert_eq!(compare_values(&num, &strnum), Ordering::Equal);
+        // Anything against a string constant compares as strings
+        assert_eq!(compare_values(&num, &string), Ordering::Equal);
+        assert_eq!(compare_values(&strnum, &string), Ordering::Less);
+        assert_eq!(
+            compare_values(&Value::Number(9.0), &string),
+            Ordering::Greater
+        );
+        // Input that does not look numeric is a string
+        assert!(!text.is_numeric_string());
+        assert_eq!(compare_values(&num, &text), Ordering::Less);
+        // Uninitialized compares as both "" and 0
+        assert_eq!(
+            compare_values(&Value::Uninitialized, &strnum),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_values(&Value::Uninitialized, &Value::from_string(String::new())),
+            Ordering::Equal
+        );
+    }
+
     #[test]
     fn test_itoa_fast() {
         assert_eq!(itoa_fast(0), "0");
@@ -487,21 +667,21 @@ mod tests {
 
     #[test]
     fn test_numeric_string_with_exponent() {
-        let v = Value::from_string("1e5".to_string());
+        let v = Value::from_input("1e5".to_string());
         assert!(v.is_numeric_string());
         assert_eq!(v.to_number(), 1e5);
     }
 
     #[test]
     fn test_numeric_string_with_sign() {
-        let v = Value::from_string("-42.5".to_string());
+        let v = Value::from_input("-42.5".to_string());
         assert!(v.is_numeric_string());
         assert_eq!(v.to_number(), -42.5);
     }
 
     #[test]
     fn test_numeric_string_whitespace() {
-        let v = Value::from_string("  123  ".to_string());
+        let v = Value::from_input("  123  ".to_string());
         assert!(v.is_numeric_string());
         assert_eq!(v.to_number(), 123.0);
     }
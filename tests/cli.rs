@@ -74,12 +74,61 @@ fn test_cli_field_separator_attached() {
     assert_eq!(output, "b\n");
 }
 
+#[test]
+fn test_cli_field_separator_tab() {
+    let output = run_awk_rs(&["-Ft", "{ print $2 }"], Some("a b\tc")).unwrap();
+    assert_eq!(output, "c\n");
+    let output = run_awk_rs(&["-F", "\\t", "{ print $2 }"], Some("a b\tc")).unwrap();
+    assert_eq!(output, "c\n");
+}
+
+#[test]
+fn test_cli_long_options() {
+    let output = run_awk_rs(
+        &[
+            "--field-separator=:",
+            "--assign=x=1",
+            "--assign",
+            "y=2",
+            "{ print $2, x + y }",
+        ],
+        Some("a:b"),
+    )
+    .unwrap();
+    assert_eq!(output, "b 3\n");
+
+    let mut file = NamedTempFile::new().unwrap();
+    writeln!(file, r#"BEGIN {{ print "long file" }}"#).unwrap();
+    let path = file.path().to_str().unwrap();
+    let output = run_awk_rs(&[&format!("--file={}", path)], None).unwrap();
+    assert_eq!(output, "long file\n");
+}
+
+#[test]
+fn test_cli_grouped_short_options() {
+    let output = run_awk_rs(&["-PvX=1", "BEGIN { print X }"], None).unwrap();
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn test_cli_variable_escapes() {
+    let output = run_awk_rs(&["-v", "x=a\\tb", "BEGIN { print x }"], None).unwrap();
+    assert_eq!(output, "a\tb\n");
+}
+
 #[test]
 fn test_cli_variable() {
     let output = run_awk_rs(&["-v", "x=5", "BEGIN { print x }"], None).unwrap();
     assert_eq!(output, "5\n");
 }
 
+#[test]
+fn test_cli_variable_is_strnum() {
+    let program = "BEGIN { print (x > 9), (x > \"9\"), (ARGV[1] > 9) }";
+    let output = run_awk_rs(&["-v", "x=10", program, "10"], None).unwrap();
+    assert_eq!(output, "1 0 1\n");
+}
+
 #[test]
 fn test_cli_program_file() {
     let mut file = NamedTempFile::new().unwrap();
@@ -90,6 +139,99 @@ fn test_cli_program_file() {
     assert_eq!(output, "from file\n");
 }
 
+#[test]
+fn test_cli_include_directive() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib_dir = dir.path().join("lib");
+    std::fs::create_dir(&lib_dir).unwrap();
+    std::fs::write(
+        lib_dir.join("greet.awk"),
+        "function greet(who) { return \"hello, \" who }\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("main.awk"),
+        "@include \"greet\"\n@include \"greet.awk\"\nBEGIN { print greet(\"world\") }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_awk-rs"))
+        .arg("-f")
+        .arg(dir.path().join("main.awk"))
+        .env("AWKPATH", &lib_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello, world\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_awk-rs"))
+        .args(["-i", "greet", "BEGIN { print greet(\"lib\") }"])
+        .env("AWKPATH", &lib_dir)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello, lib\n");
+}
+
+#[test]
+fn test_cli_include_error_names_library_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("broken.awk");
+    std::fs::write(&lib, "function f() {\n    return g()\n}\n").unwrap();
+
+    let err = run_awk_rs(&["-i", lib.to_str().unwrap(), "BEGIN { f() }"], None).unwrap_err();
+    assert!(
+        err.contains(&format!("{}, line 2", lib.display())),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_cli_multiple_program_files() {
+    let mut lib = NamedTempFile::new().unwrap();
+    writeln!(lib, "function double(x) {{ return 2 * x }}").unwrap();
+    let mut main = NamedTempFile::new().unwrap();
+    writeln!(main, "BEGIN {{ print double(21) }}").unwrap();
+
+    let output = run_awk_rs(
+        &[
+            "-f",
+            lib.path().to_str().unwrap(),
+            "-e",
+            "BEGIN { print \"source\" }",
+            "-f",
+            main.path().to_str().unwrap(),
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "source\n42\n");
+}
+
+#[test]
+fn test_cli_program_from_stdin() {
+    let output = run_awk_rs(
+        &["-e", "function f() { return \"ok\" }", "-f", "-"],
+        Some("BEGIN { print f() }\n"),
+    )
+    .unwrap();
+    assert_eq!(output, "ok\n");
+}
+
+#[test]
+fn test_cli_fragment_error_location() {
+    let mut lib = NamedTempFile::new().unwrap();
+    writeln!(
+        lib,
+        "function ok() {{ return 1 }}\nfunction bad() {{ x = }}"
+    )
+    .unwrap();
+    let path = lib.path().to_str().unwrap();
+
+    let err = run_awk_rs(&["-f", path, "-e", "BEGIN { ok() }"], None).unwrap_err();
+    assert!(err.contains(&format!("{}, line 2", path)), "{}", err);
+}
+
 #[test]
 fn test_cli_posix_mode() {
     // In posix mode, FPAT should not work
@@ -97,6 +239,39 @@ fn test_cli_posix_mode() {
     assert_eq!(output, "posix\n");
 }
 
+#[test]
+fn test_cli_posix_ignores_ignorecase() {
+    let program = "BEGIN { IGNORECASE = 1; print (\"ABC\" ~ /b/) }";
+    let output = run_awk_rs(&[program], None).unwrap();
+    assert_eq!(output, "1\n");
+    let output = run_awk_rs(&["--posix", program], None).unwrap();
+    assert_eq!(output, "0\n");
+    let output = run_awk_rs(&["--traditional", program], None).unwrap();
+    assert_eq!(output, "0\n");
+}
+
+#[test]
+fn test_cli_posix_rejects_gawk_functions() {
+    let program = r#"BEGIN { print gensub("a", "b", "g", "aa") }"#;
+    let err = run_awk_rs(&["--posix", program], None).unwrap_err();
+    assert!(
+        err.contains("gensub() is a gawk extension and is not allowed in --posix mode"),
+        "{}",
+        err
+    );
+    let err = run_awk_rs(&["--traditional", "BEGINFILE { }"], None).unwrap_err();
+    assert!(err.contains("--traditional mode"), "{}", err);
+}
+
+#[test]
+fn test_cli_posix_gawk_variables_are_plain() {
+    let program = r#"BEGIN { FPAT = "[0-9]+" } { print $1, RT "|" PROCINFO["version"] "|" }"#;
+    let output = run_awk_rs(&[program], Some("a1 b2\n")).unwrap();
+    assert_eq!(output, format!("1 \n|{}|\n", env!("CARGO_PKG_VERSION")));
+    let output = run_awk_rs(&["--posix", program], Some("a1 b2\n")).unwrap();
+    assert_eq!(output, "a1 ||\n");
+}
+
 #[test]
 fn test_cli_traditional_mode() {
     let output = run_awk_rs(&["--traditional", "BEGIN { print \"trad\" }"], None).unwrap();
@@ -129,6 +304,226 @@ fn test_cli_multiple_inputs() {
     assert!(output.contains("a") && output.contains("b"));
 }
 
+#[test]
+fn test_cli_begin_end_once_across_files() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a\nb").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "c").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &[
+            r#"BEGIN { print "begin" } { print FNR, NR, $0 } END { print "end", NR }"#,
+            path1,
+            path2,
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "begin\n1 1 a\n2 2 b\n1 3 c\nend 3\n");
+}
+
+#[test]
+fn test_cli_beginfile_per_file() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "b").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &[
+            r#"BEGINFILE { n++ } ENDFILE { print FILENAME == ARGV[n] }"#,
+            path1,
+            path2,
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "1\n1\n");
+}
+
+#[test]
+fn test_cli_exit_stops_remaining_files() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "b").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &[r#"{ print; exit } END { print "end" }"#, path1, path2],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "a\nend\n");
+}
+
+#[test]
+fn test_cli_assignment_operands() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "b").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &[
+            "{ print x, $0 } END { print x }",
+            "x=1",
+            path1,
+            "x=2",
+            path2,
+            "x=3",
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "1 a\n2 b\n3\n");
+}
+
+#[test]
+fn test_cli_assignment_operand_before_stdin() {
+    let output = run_awk_rs(&["{ print $2 }", "FS=:"], Some("a:b")).unwrap();
+    assert_eq!(output, "b\n");
+}
+
+#[test]
+fn test_cli_argv_edited_in_begin() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "b").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let program = format!(
+        r#"BEGIN {{ ARGV[1] = ""; ARGV[ARGC++] = "{}" }} {{ print }}"#,
+        path1
+    );
+    let output = run_awk_rs(&[&program, path1, path2], None).unwrap();
+    assert_eq!(output, "b\na\n");
+}
+
+#[test]
+fn test_cli_argc_lowered_in_begin() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &["BEGIN { ARGC = 2 } { print }", path1, "/nonexistent/file"],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "a\n");
+}
+
+#[test]
+fn test_cli_unreadable_operand_is_skipped() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_awk-rs"))
+        .args(["{ print } END { print NR }", "/nonexistent/file", path1])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a\n1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("warning: cannot open file `/nonexistent/file' for reading"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_stdin_dash_between_files() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let output = run_awk_rs(&["{ print FILENAME == \"-\", $0 }", path1, "-"], Some("b")).unwrap();
+    assert_eq!(output, "0 a\n1 b\n");
+}
+
+#[test]
+fn test_cli_getline_crosses_files() {
+    let mut file1 = NamedTempFile::new().unwrap();
+    writeln!(file1, "a").unwrap();
+    let mut file2 = NamedTempFile::new().unwrap();
+    writeln!(file2, "b").unwrap();
+
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let output = run_awk_rs(
+        &[
+            r#"{ getline line; print $0, line, NR, FNR, (FILENAME == ARGV[2]) }"#,
+            path1,
+            path2,
+        ],
+        None,
+    )
+    .unwrap();
+    assert_eq!(output, "a b 2 1 1\n");
+}
+
+#[test]
+fn test_cli_getline_from_dev_stdin() {
+    let output = run_awk_rs(
+        &[r#"BEGIN { while ((getline line < "/dev/stdin") > 0) print "got", line }"#],
+        Some("a\nb\n"),
+    )
+    .unwrap();
+    assert_eq!(output, "got a\ngot b\n");
+}
+
+#[test]
+fn test_cli_getline_shares_stdin_with_main_input() {
+    let output = run_awk_rs(&[r#"{ getline x < "-"; print $0, x }"#], Some("1\n2\n3\n")).unwrap();
+    assert_eq!(output, "1 2\n3 2\n");
+}
+
+#[test]
+fn test_cli_print_to_inherited_fd() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fd3.txt");
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(r#"exec 3>"$1"; "$0" 'BEGIN { print "one" > "/dev/fd/3" }'; echo two >&3"#)
+        .arg(env!("CARGO_BIN_EXE_awk-rs"))
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_awk-rs"))
+        .arg(r#"BEGIN { print "x" > "/dev/fd/9" }"#)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_stderr_ordering() {
+    let output = Command::new("cargo")
+        .args(["run", "--quiet", "--"])
+        .arg(r#"BEGIN { print "one"; print "two" > "/dev/stderr"; print "three" > "/dev/fd/2" }"#)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "two\nthree\n");
+}
+
 #[test]
 fn test_cli_error_no_program() {
     let result = run_awk_rs(&[], None);
@@ -141,6 +536,34 @@ fn test_cli_error_unknown_option() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_cli_usage_error_exit_status() {
+    let output = Command::new(env!("CARGO_BIN_EXE_awk-rs"))
+        .args(["--bogus", "BEGIN { }"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unrecognized option '--bogus'"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("--help"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_non_decimal_data() {
+    let program = "{ print $1 + 0, $2 + 0, $3 + 0 }";
+    let input = "0x1A 011 0x1G\n";
+    let output = run_awk_rs(&[program], Some(input)).unwrap();
+    assert_eq!(output, "0 11 0\n");
+    let output = run_awk_rs(&["--non-decimal-data", program], Some(input)).unwrap();
+    assert_eq!(output, "26 9 0\n");
+    let output = run_awk_rs(&["-n", "--posix", program], Some(input)).unwrap();
+    assert_eq!(output, "0 11 0\n");
+}
+
 #[test]
 fn test_cli_error_missing_f_arg() {
     let result = run_awk_rs(&["-f"], None);
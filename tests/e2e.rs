@@ -275,11 +275,47 @@ fn test_string_comparison() {
 
 #[test]
 fn test_numeric_string_comparison() {
-    // Both are numeric strings, should compare numerically
-    let output = run_awk(r#"BEGIN { print ("10" > "9") }"#, "").unwrap();
+    // Fields that look like numbers are numeric strings and compare numerically
+    let output = run_awk(r#"{ print ($1 > $2) }"#, "10 9").unwrap();
     assert_eq!(output, "1\n");
 }
 
+#[test]
+fn test_string_constant_comparison() {
+    // String constants are strings, even when they look numeric
+    let output = run_awk(r#"BEGIN { print ("10" < "9"), ("1e2" == "100") }"#, "").unwrap();
+    assert_eq!(output, "1 0\n");
+}
+
+#[test]
+fn test_strnum_comparison_matrix() {
+    let output = run_awk(
+        r#"{
+            print ($1 == 10), ($1 == "10"), ($1 == $2), ($1 < "9")
+            x = $1 ""; print (x < 9), (x == 10)
+            y = $1 + 0; print (y == $2)
+        }"#,
+        "10 1e1",
+    )
+    .unwrap();
+    assert_eq!(output, "1 1 1 1\n1 1\n1\n");
+}
+
+#[test]
+fn test_strnum_from_input_sources() {
+    let output = run_awk(
+        r#"{
+            n = split("10 9", parts, " ")
+            print (parts[1] > parts[2])
+            print (substr($0, 1, 2) > substr($0, 4, 1))
+            getline line; print (line > 9)
+        }"#,
+        "10 9\n10",
+    )
+    .unwrap();
+    assert_eq!(output, "1\n0\n1\n");
+}
+
 // ============================================================================
 // Control Flow Tests
 // ============================================================================
@@ -1178,6 +1214,77 @@ fn test_paragraph_mode_nf() {
     assert!(output.contains("2 2 word4 word5"));
 }
 
+// === Record Separators (RS) ===
+
+#[test]
+fn test_rs_single_char() {
+    let output = run_awk(r#"BEGIN { RS = ";" } { print NR ": " $0 }"#, "a;b;c").unwrap();
+    assert_eq!(output, "1: a\n2: b\n3: c\n");
+}
+
+#[test]
+fn test_rs_nul_delimited() {
+    let output = run_awk(r#"BEGIN { RS = "\0" } { print $0 }"#, "one\0two\0").unwrap();
+    assert_eq!(output, "one\ntwo\n");
+}
+
+#[test]
+fn test_rs_regex() {
+    let output = run_awk(
+        r#"BEGIN { RS = "\r\n|\n" } { print NR, $0 }"#,
+        "a\r\nb\nc\r\n",
+    )
+    .unwrap();
+    assert_eq!(output, "1 a\n2 b\n3 c\n");
+}
+
+#[test]
+fn test_rs_multi_char_literal() {
+    let output = run_awk(r#"BEGIN { RS = "--" } { print $0 }"#, "x--y--z").unwrap();
+    assert_eq!(output, "x\ny\nz\n");
+}
+
+#[test]
+fn test_rs_changed_mid_stream() {
+    let output = run_awk(r#"NR == 1 { RS = ";" } { print $0 }"#, "a\nb;c;d").unwrap();
+    assert_eq!(output, "a\nb\nc\nd\n");
+}
+
+#[test]
+fn test_rt_round_trip_regex() {
+    let input = "a;;b;c\r\nd";
+    let output = run_awk(
+        r#"BEGIN { RS = ";+|\r?\n" } { printf "%s%s", $0, RT }"#,
+        input,
+    )
+    .unwrap();
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_rt_round_trip_newlines() {
+    let input = "one\r\ntwo\nthree";
+    let output = run_awk(r#"{ printf "%s%s", $0, RT }"#, input).unwrap();
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_rt_paragraph_mode() {
+    let input = "a\nb\n\n\nc\n";
+    let output = run_awk(r#"BEGIN { RS = "" } { printf "%s%s", $0, RT }"#, input).unwrap();
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_rt_getline_var() {
+    let output = run_awk(
+        r#"BEGIN { RS = ";"; "printf \"a;b\"" | getline line; print line "[" RT "]" }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "a[;]\n");
+}
+
 // === cmd | getline ===
 
 #[test]
@@ -1208,6 +1315,195 @@ fn test_pipe_getline_no_var() {
     assert_eq!(output, "test\n");
 }
 
+// === Coprocesses (|&) ===
+
+#[test]
+fn test_coprocess_sort() {
+    let output = run_awk(
+        r#"BEGIN {
+            cmd = "sort"
+            print "b" |& cmd; print "c" |& cmd; print "a" |& cmd
+            close(cmd, "to")
+            while ((cmd |& getline line) > 0) print line
+            close(cmd)
+        }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "a\nb\nc\n");
+}
+
+#[test]
+fn test_coprocess_line_at_a_time() {
+    let output = run_awk(
+        r#"BEGIN { cmd = "cat"; for (i = 1; i <= 3; i++) { printf "%d\n", i * i |& cmd; cmd |& getline; print } }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "1\n4\n9\n");
+}
+
+#[test]
+fn test_coprocess_large_output() {
+    // The command's output must be drained while we are still writing
+    let output = run_awk(
+        r#"BEGIN { cmd = "cat"; for (i = 0; i < 20000; i++) print "xxxxxxxxxxxxxxxxxxxx" |& cmd; close(cmd, "to"); while ((cmd |& getline line) > 0) n++; print n }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "20000\n");
+}
+
+#[test]
+fn test_coprocess_write_after_close_to() {
+    let result = run_awk(
+        r#"BEGIN { cmd = "cat"; print "a" |& cmd; close(cmd, "to"); print "b" |& cmd }"#,
+        "",
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_coprocess_write_after_close_from() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    let program = format!(
+        r#"BEGIN {{
+            cmd = "cat > {0}"
+            print "a" |& cmd
+            print close(cmd, "from")
+            print "b" |& cmd
+            print close(cmd, "to")
+            while ((getline line < "{0}") > 0) print line
+        }}"#,
+        path.display()
+    );
+    let output = run_awk(&program, "").unwrap();
+    assert_eq!(output, "0\n0\na\nb\n");
+}
+
+// === close() ===
+
+#[test]
+fn test_close_output_pipe_returns_status() {
+    let output = run_awk(
+        r#"BEGIN { cmd = "cat >/dev/null; exit 3"; print "x" | cmd; print close(cmd) }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "3\n");
+}
+
+#[test]
+fn test_close_input_pipe_returns_status() {
+    let output = run_awk(
+        r#"BEGIN { cmd = "echo hi; exit 7"; cmd | getline; print $0, close(cmd) }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "hi 7\n");
+}
+
+#[test]
+fn test_close_killed_by_signal() {
+    let output = run_awk(
+        r#"BEGIN { cmd = "kill -9 $$"; cmd | getline; print close(cmd) }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "265\n");
+}
+
+#[test]
+fn test_close_waits_for_output_pipe() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    let program = format!(
+        r#"BEGIN {{ cmd = "sort > {0}"; print "b" | cmd; print "a" | cmd; close(cmd); while ((getline line < "{0}") > 0) print line }}"#,
+        path.display()
+    );
+    let output = run_awk(&program, "").unwrap();
+    assert_eq!(output, "a\nb\n");
+}
+
+#[test]
+fn test_close_unknown_returns_minus_one() {
+    let output = run_awk(r#"BEGIN { print close("not-open") }"#, "").unwrap();
+    assert_eq!(output, "-1\n");
+}
+
+// === Special File Names ===
+
+#[test]
+fn test_print_to_dev_stdout() {
+    let output = run_awk(
+        r#"BEGIN { print "a"; print "b" > "/dev/stdout"; print "c" >> "/dev/stdout"; print "d" }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "a\nb\nc\nd\n");
+}
+
+#[test]
+fn test_print_to_dash_is_stdout() {
+    let output = run_awk(
+        r#"BEGIN { printf "%s\n", "x" > "-"; print close("-") }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "x\n0\n");
+}
+
+#[test]
+fn test_print_to_dev_stderr_not_in_stdout() {
+    let output = run_awk(
+        r#"BEGIN { print "out"; print "err" > "/dev/stderr"; print close("/dev/stderr") }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "out\n0\n");
+}
+
+// === Plain getline ===
+
+#[test]
+fn test_getline_skips_header() {
+    let output = run_awk(r#"NR == 1 { getline } { print NR, $1 }"#, "name\na b\nc d").unwrap();
+    assert_eq!(output, "2 a\n3 c\n");
+}
+
+#[test]
+fn test_getline_var_keeps_record() {
+    let output = run_awk(
+        r#"NR == 1 { getline line; print $0, NF, line, NR, FNR }"#,
+        "x y\nz",
+    )
+    .unwrap();
+    assert_eq!(output, "x y 2 z 2 2\n");
+}
+
+#[test]
+fn test_getline_var_loop() {
+    let output = run_awk(
+        r#"{ n = 1; while ((getline line) > 0) n++; print n, NR }"#,
+        "a\nb\nc",
+    )
+    .unwrap();
+    assert_eq!(output, "3 3\n");
+}
+
+#[test]
+fn test_getline_in_begin_reads_main_input() {
+    let output = run_awk(r#"BEGIN { getline; print "first:", $1 } { print }"#, "a\nb").unwrap();
+    assert_eq!(output, "first: a\nb\n");
+}
+
+#[test]
+fn test_getline_at_eof_returns_zero() {
+    let output = run_awk(r#"END { print getline, $0 }"#, "last").unwrap();
+    assert_eq!(output, "0 last\n");
+}
+
 // === Array by Reference ===
 
 #[test]
@@ -1228,6 +1524,204 @@ fn test_array_in_function() {
     assert_eq!(output, "modified\n");
 }
 
+#[test]
+fn test_array_by_reference_same_name_and_nested_calls() {
+    let program = r#"
+        function add(a, k) { a[k] = 1 }
+        function outer(b) { add(b, "inner"); b["outer"] = 1 }
+        BEGIN {
+            a["x"] = 1
+            add(a, "y")
+            outer(a)
+            print length(a)
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "4\n");
+}
+
+#[test]
+fn test_local_arrays_per_call() {
+    let program = r#"
+        function depth(n, seen) {
+            seen[n] = 1
+            if (n > 0) depth(n - 1)
+            return length(seen)
+        }
+        BEGIN { print depth(3), length(seen) }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "1 0\n");
+}
+
+#[test]
+fn test_untyped_argument_becomes_array() {
+    let program = r#"
+        function fill(r) { r["a"] = 1; r["b"] = 2 }
+        BEGIN {
+            fill(result)
+            for (k in result) n++
+            print n, isarray(result)
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "2 1\n");
+}
+
+#[test]
+fn test_untyped_local_passed_down() {
+    let program = r#"
+        function add(t, k) { t[k] = 1 }
+        function collect(s,   seen, n, i) {
+            n = split(s, parts)
+            for (i = 1; i <= n; i++) add(seen, parts[i])
+            return length(seen)
+        }
+        BEGIN { print collect("a b a c b") }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "3\n");
+}
+
+#[test]
+fn test_untyped_argument_used_as_scalar() {
+    let program = r#"
+        function f(p) { p = 5; return p }
+        BEGIN { print f(u), typeof(u); u[1] = 1; print length(u) }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "5 untyped\n1\n");
+}
+
+#[test]
+fn test_array_parameter_hides_global_scalar() {
+    let program = r#"
+        function f(x) { return typeof(x) }
+        BEGIN { x = 5; a[1] = 1; print f(a), x }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "array 5\n");
+}
+
+#[test]
+fn test_scalar_array_misuse_errors() {
+    for (program, message) in [
+        (
+            "BEGIN { a[1] = 1; a = 2 }",
+            "attempt to use array `a' in a scalar context",
+        ),
+        (
+            "BEGIN { a[1] = 1; print a }",
+            "attempt to use array `a' in a scalar context",
+        ),
+        (
+            "BEGIN { x = 1; x[1] = 2 }",
+            "attempt to use scalar `x' as an array",
+        ),
+        (
+            "BEGIN { x = 1; for (k in x) print k }",
+            "attempt to use scalar `x' as an array",
+        ),
+        (
+            "function f(p) { p[1] = 1 } BEGIN { x = 1; f(x) }",
+            "attempt to use scalar `p' as an array",
+        ),
+        (
+            "function f(p) { return p + 1 } BEGIN { a[1] = 1; f(a) }",
+            "attempt to use array `p' in a scalar context",
+        ),
+    ] {
+        let err = run_awk(program, "").unwrap_err();
+        assert!(err.contains(message), "{}: {}", program, err);
+    }
+}
+
+#[test]
+fn test_scalar_as_array_errors_have_locations() {
+    for (program, location) in [
+        ("BEGIN { x = 1; x[1] = 2 }", "line 1, column 17"),
+        ("BEGIN { x = 1; y = x[1] }", "line 1, column 21"),
+        ("BEGIN { x = 1; delete x[1] }", "line 1, column 16"),
+        ("BEGIN { x = 1; print (1 in x) }", "line 1, column 28"),
+        ("BEGIN { x = 1\n for (k in x) print k }", "line 2, column 2"),
+        (
+            "BEGIN { x = 1; n = split(\"a b\", x) }",
+            "line 1, column 20",
+        ),
+    ] {
+        let err = run_awk(program, "").unwrap_err();
+        let expected = format!("at {}: attempt to use scalar `x' as an array", location);
+        assert!(err.contains(&expected), "{}: {}", program, err);
+    }
+}
+
+// === Arrays of Arrays ===
+
+#[test]
+fn test_array_of_arrays_basic() {
+    let program = r#"BEGIN {
+        a["x"]["y"] = 1
+        a["x"]["z"] = 2
+        a["w"] = 3
+        print a["x"]["y"] + a["x"]["z"], length(a), length(a["x"])
+        print ("y" in a["x"]), ("w" in a["x"]), ("x" in a)
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "3 2 2\n1 0 1\n");
+}
+
+#[test]
+fn test_array_of_arrays_for_in_and_delete() {
+    let program = r#"BEGIN {
+        m[1][1] = "a"; m[1][2] = "b"; m[2][1] = "c"
+        for (k in m[1]) n++
+        delete m[1][1]
+        delete m[2]
+        print n, length(m[1]), (2 in m), length(m)
+        delete m[1]
+        print length(m)
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "2 1 0 1\n0\n");
+}
+
+#[test]
+fn test_array_of_arrays_deep_and_multi_subscript() {
+    let program = r#"BEGIN {
+        t["a"]["b"]["c"] = "deep"
+        t["p"][1, 2] = "pair"
+        print t["a"]["b"]["c"], t["p"][1, 2], length(t["a"]["b"])
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "deep pair 1\n");
+}
+
+#[test]
+fn test_subarray_passed_to_function() {
+    let program = r#"
+        function fill(s, n,   i) { for (i = 1; i <= n; i++) s[i] = i * i; return length(s) }
+        function sum(s,   k, t) { for (k in s) t += s[k]; return t }
+        BEGIN {
+            data["sq"]["seed"] = 0
+            fill(data["sq"], 3)
+            print length(data["sq"]), sum(data["sq"])
+            n = split("a:b", data["parts"], ":")
+            print n, data["parts"][2]
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "4 14\n2 b\n");
+}
+
+#[test]
+fn test_array_of_arrays_type_errors() {
+    let err = run_awk(r#"BEGIN { a["x"]["y"] = 1; print a["x"] }"#, "").unwrap_err();
+    assert!(
+        err.contains("attempt to use array `a[\"x\"]' in a scalar context"),
+        "{}",
+        err
+    );
+    let err = run_awk(r#"BEGIN { a["x"] = 1; a["x"]["y"] = 1 }"#, "").unwrap_err();
+    assert!(
+        err.contains("attempt to use scalar `a[\"x\"]' as an array"),
+        "{}",
+        err
+    );
+}
+
 // === FILENAME Variable ===
 
 #[test]
@@ -1238,6 +1732,26 @@ fn test_filename_variable() {
     assert!(output.contains("test"));
 }
 
+#[test]
+fn test_unreadable_operand_is_reported_as_warning() {
+    let tokens = Lexer::new("{ print } END { print NR }").tokenize().unwrap();
+    let ast = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::new(&ast);
+    interpreter.set_args(vec!["awk".to_string(), "/nonexistent/file".to_string()]);
+
+    let mut output = Vec::new();
+    let status = interpreter.run_argv(&mut output).unwrap();
+    assert_eq!(status, 2);
+    assert_eq!(String::from_utf8(output).unwrap(), "0\n");
+    let warnings = interpreter.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0].starts_with("cannot open file `/nonexistent/file' for reading"),
+        "{}",
+        warnings[0]
+    );
+}
+
 // === UTF-8 / Unicode Support ===
 
 #[test]
@@ -1266,6 +1780,528 @@ fn test_utf8_index() {
     assert_eq!(output, "6\n");
 }
 
+// === POSIX ERE Translation ===
+
+#[test]
+fn test_regex_interval() {
+    let output = run_awk(r#"/^a{2}$/ { print "yes: " $0 }"#, "a\naa\naaa").unwrap();
+    assert_eq!(output, "yes: aa\n");
+}
+
+#[test]
+fn test_regex_escaped_slash() {
+    let output = run_awk(r#"/\/usr\// { print $0 }"#, "/usr/bin\n/opt").unwrap();
+    assert_eq!(output, "/usr/bin\n");
+}
+
+#[test]
+fn test_dynamic_regex_escaped_dot() {
+    let output = run_awk(r#"{ print ($0 ~ "a\\.b") }"#, "a.b\naxb").unwrap();
+    assert_eq!(output, "1\n0\n");
+}
+
+#[test]
+fn test_regex_literal_brace() {
+    let output = run_awk(r#"{ gsub(/{/, "<"); print }"#, "f{x}").unwrap();
+    assert_eq!(output, "f<x}\n");
+}
+
+#[test]
+fn test_regex_bracket_with_close_bracket() {
+    let output = run_awk(r#"{ gsub(/[]a]/, "_"); print }"#, "a]b").unwrap();
+    assert_eq!(output, "__b\n");
+    let output = run_awk(r#"{ gsub(/[a\]]/, "_"); print }"#, "a]b").unwrap();
+    assert_eq!(output, "__b\n");
+}
+
+#[test]
+fn test_regex_dot_matches_newline() {
+    let output = run_awk(r#"BEGIN { s = "a\nb"; print (s ~ /a.b/) }"#, "").unwrap();
+    assert_eq!(output, "1\n");
+}
+
+#[test]
+fn test_regex_error_names_pattern() {
+    let err = run_awk(r#"{ print match($0, "[abc") }"#, "x").unwrap_err();
+    assert!(err.contains("/[abc/"), "{}", err);
+}
+
+// === IGNORECASE ===
+
+#[test]
+fn test_ignorecase_rule_pattern() {
+    let output = run_awk(r#"BEGIN { IGNORECASE = 1 } /hello/"#, "HELLO\nbye\nHeLLo").unwrap();
+    assert_eq!(output, "HELLO\nHeLLo\n");
+}
+
+#[test]
+fn test_ignorecase_match_operators() {
+    let output = run_awk(
+        r#"BEGIN { print ("ABC" ~ /b/); IGNORECASE = 1; print ("ABC" ~ /b/), ("ABC" !~ "b") }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "0\n1 0\n");
+}
+
+#[test]
+fn test_ignorecase_comparisons() {
+    let output = run_awk(
+        r#"BEGIN {
+            print ("ABC" == "abc"), ("abc" < "ABD")
+            IGNORECASE = 1
+            print ("ABC" == "abc"), ("abc" < "ABD"), ("ABC" != "abc"), (10 < 9)
+        }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "0 0\n1 1 0 0\n");
+}
+
+#[test]
+fn test_ignorecase_builtins() {
+    let output = run_awk(
+        r#"BEGIN {
+            IGNORECASE = 1
+            s = "Foo fOO"
+            print match(s, /o+/), RSTART, RLENGTH
+            n = gsub(/foo/, "x", s); print n, s
+            print gensub("b", "-", "g", "aBcb")
+            print split("1A2a3", parts, "a"), parts[2]
+            print index("Hello", "LL")
+        }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "2 2 2\n2 x x\na-c-\n3 2\n3\n");
+}
+
+#[test]
+fn test_ignorecase_field_splitting() {
+    let output = run_awk(
+        r#"BEGIN { IGNORECASE = 1; FS = "x" } { print $2 }"#,
+        "1X2x3",
+    )
+    .unwrap();
+    assert_eq!(output, "2\n");
+    let output = run_awk(
+        r#"BEGIN { IGNORECASE = 1; FS = "sep" } { print NF }"#,
+        "aSEPbsepc",
+    )
+    .unwrap();
+    assert_eq!(output, "3\n");
+}
+
+#[test]
+fn test_ignorecase_rs() {
+    let output = run_awk(r#"BEGIN { IGNORECASE = 1; RS = "x" } { print }"#, "1X2x3").unwrap();
+    assert_eq!(output, "1\n2\n3\n");
+}
+
+#[test]
+fn test_ignorecase_toggled_at_runtime() {
+    let output = run_awk(
+        r#"{ IGNORECASE = (NR == 2); print /a/ ? "y" : "n" }"#,
+        "A\nA\nA",
+    )
+    .unwrap();
+    assert_eq!(output, "n\ny\nn\n");
+}
+
+// === switch/case ===
+
+#[test]
+fn test_switch_cases_and_default() {
+    let program = r#"{
+        switch ($1) {
+        case 1:
+            print "one"
+            break
+        case "two":
+            print "two"
+            break
+        case /^t/:
+            print "t-word"
+            break
+        default:
+            print "other"
+        }
+    }"#;
+    let output = run_awk(program, "1\ntwo\nthree\nfour\n").unwrap();
+    assert_eq!(output, "one\ntwo\nt-word\nother\n");
+}
+
+#[test]
+fn test_switch_fall_through() {
+    let program = r#"BEGIN {
+        for (i = 1; i <= 3; i++) {
+            switch (i) {
+            case 1:
+            case 2:
+                printf "low "
+            case 3:
+                print i
+            }
+        }
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "low 1\nlow 2\n3\n");
+}
+
+#[test]
+fn test_switch_default_not_last() {
+    let program = r#"BEGIN { switch (5) { default: print "default" case 1: print "one" } }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "default\none\n");
+}
+
+#[test]
+fn test_switch_continue_and_return_leave_switch() {
+    let program = r#"
+        function kind(n) {
+            switch (n % 2) {
+            case 0: return "even"
+            default: return "odd"
+            }
+        }
+        BEGIN {
+            for (i = 1; i <= 4; i++) {
+                switch (i) { case 2: continue }
+                print i, kind(i)
+            }
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "1 odd\n3 odd\n4 even\n");
+}
+
+#[test]
+fn test_switch_duplicate_case_is_error() {
+    let err = run_awk(r#"BEGIN { switch (x) { case "a": case "a": } }"#, "").unwrap_err();
+    assert!(err.contains("duplicate case values"), "{}", err);
+    let err = run_awk("BEGIN { switch (x) { default: default: } }", "").unwrap_err();
+    assert!(err.contains("more than one default"), "{}", err);
+}
+
+// === typeof and isarray ===
+
+#[test]
+fn test_typeof_scalars() {
+    let program = r#"{
+        n = 1; s = "1"; u = unset
+        print typeof(n), typeof(s), typeof($1), typeof($2), typeof(u), typeof(never)
+        print typeof(1 + 1), typeof("a" "b"), typeof(@/re/)
+    }"#;
+    let output = run_awk(program, "42 abc\n").unwrap();
+    assert_eq!(
+        output,
+        "number string strnum string unassigned untyped\nnumber string regexp\n"
+    );
+}
+
+#[test]
+fn test_typeof_unassigned_after_rvalue_use() {
+    let output = run_awk(r#"BEGIN { y = z; print typeof(z) }"#, "").unwrap();
+    assert_eq!(output, "unassigned\n");
+    let output = run_awk(r#"BEGIN { x; print typeof(x), typeof(w) }"#, "").unwrap();
+    assert_eq!(output, "unassigned untyped\n");
+
+    // Once used as a scalar, a name can no longer become an array
+    let result = run_awk(r#"function f(a) { a[1] = 1 } BEGIN { x; f(x) }"#, "");
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_typeof_regexp_variables() {
+    // A typed regexp keeps its type when assigned, stored or passed
+    let output = run_awk(
+        r#"function t(p) { return typeof(p) }
+        BEGIN {
+            x = @/ab/; a[1] = @/c/
+            print typeof(x), typeof(a[1]), t(x), typeof(x "")
+            print ("cabd" ~ x), split("1ab2", parts, x)
+        }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "regexp regexp regexp string\n1 2\n");
+}
+
+#[test]
+fn test_typeof_arrays_and_elements() {
+    let program = r#"BEGIN {
+        a["x"]["y"] = 1; a["s"] = "t"
+        print typeof(a), typeof(a["x"]), typeof(a["x"]["y"]), typeof(a["s"]), typeof(a["none"])
+        print isarray(a), isarray(a["x"]), isarray(a["s"]), isarray(v)
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "array array number string untyped\n1 1 0 0\n");
+}
+
+#[test]
+fn test_rvalue_reference_creates_element() {
+    let program = r#"BEGIN {
+        x = a[1]; print length(a), isarray(a), typeof(a)
+        if (a[2] == "") print length(a), (2 in a), (3 in a)
+    }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "1 1 array\n2 1 0\n");
+}
+
+#[test]
+fn test_isarray_optional_array_argument() {
+    let program = r#"
+        function count(x,   k, n) {
+            if (!isarray(x)) return typeof(x) == "untyped" ? 0 : 1
+            for (k in x) n++
+            return n
+        }
+        BEGIN { a[1]; a[1] = 1; a[2] = 2; print count(a), count(5), count() }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "2 1 0\n");
+}
+
+#[test]
+fn test_typed_regexp_constant() {
+    let output = run_awk(r#"{ if ($0 ~ @/^a+$/) print "yes: " @/a+/ }"#, "aaa\nb\n").unwrap();
+    assert_eq!(output, "yes: a+\n");
+}
+
+// === PROCINFO["sorted_in"] ===
+
+#[test]
+fn test_sorted_in_index_orders() {
+    let program = r#"
+        BEGIN {
+            a[10]; a[9]; a["x"]; a[2]
+            a[10] = a[9] = a["x"] = a[2] = 1
+            n = split("@ind_str_asc @ind_str_desc @ind_num_asc @ind_num_desc", orders)
+            for (i = 1; i <= n; i++) {
+                PROCINFO["sorted_in"] = orders[i]
+                s = ""
+                for (k in a) s = s " " k
+                print orders[i] ":" s
+            }
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(
+        output,
+        "@ind_str_asc: 10 2 9 x\n\
+         @ind_str_desc: x 9 2 10\n\
+         @ind_num_asc: x 2 9 10\n\
+         @ind_num_desc: 10 9 2 x\n"
+    );
+}
+
+#[test]
+fn test_sorted_in_value_orders() {
+    let program = r#"
+        BEGIN {
+            a["p"] = 10; a["q"] = "apple"; a["r"] = 9; a["s"] = "10"; a["t"]["sub"] = 1
+            n = split("@val_type_asc @val_str_asc @val_num_asc @val_num_desc", orders)
+            for (i = 1; i <= n; i++) {
+                PROCINFO["sorted_in"] = orders[i]
+                s = ""
+                for (k in a) s = s " " k
+                print orders[i] ":" s
+            }
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(
+        output,
+        "@val_type_asc: r p s q t\n\
+         @val_str_asc: p s r q t\n\
+         @val_num_asc: q r p s t\n\
+         @val_num_desc: t s p r q\n"
+    );
+}
+
+#[test]
+fn test_sorted_in_user_function() {
+    let program = r#"
+        function by_length(i1, v1, i2, v2) {
+            if (length(v1) != length(v2))
+                return length(v1) - length(v2)
+            return i1 < i2 ? -1 : i1 > i2
+        }
+        BEGIN {
+            a[1] = "ccc"; a[2] = "a"; a[3] = "bb"; a[4] = "d"
+            PROCINFO["sorted_in"] = "by_length"
+            for (k in a) print k, a[k]
+            PROCINFO["sorted_in"] = "@unsorted"
+            for (k in a) n++
+            print n
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "2 a\n4 d\n3 bb\n1 ccc\n4\n");
+}
+
+#[test]
+fn test_for_in_insertion_order() {
+    // Without PROCINFO["sorted_in"], elements come back in the order they were added
+    let program = r#"
+        BEGIN {
+            a["banana"] = 1; a["apple"] = 2; a[10] = 3; a["cherry"] = 4; a[2] = 5
+            delete a["apple"]
+            a["apple"] = 6
+            a["banana"] = 7
+            for (k in a) print k, a[k]
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "banana 7\n10 3\ncherry 4\n2 5\napple 6\n");
+}
+
+#[test]
+fn test_sorted_in_undefined_function() {
+    let program = r#"BEGIN { PROCINFO["sorted_in"] = "nope"; a[1] = 1; for (k in a) print k }"#;
+    let err = run_awk(program, "").unwrap_err();
+    assert!(
+        err.contains("sort comparison function `nope' is not defined"),
+        "{}",
+        err
+    );
+}
+
+// === Indirect Function Calls ===
+
+#[test]
+fn test_indirect_call_user_function() {
+    let program = r#"
+        function double(x) { return 2 * x }
+        function square(x) { return x * x }
+        function apply(f, v) { return @f(v) }
+        BEGIN {
+            ops["d"] = "double"; ops["s"] = "square"
+            for (k in ops) { f = ops[k]; print k, @f(5) }
+            print apply("square", 3)
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "d 10\ns 25\n9\n");
+}
+
+#[test]
+fn test_indirect_call_builtin() {
+    let program = r#"
+        BEGIN {
+            f = "toupper"; print @f("abc")
+            f = "split"; n = @f("a:b:c", parts, ":"); print n, parts[3]
+            f = "length"; print @f("hello")
+        }"#;
+    let output = run_awk(program, "").unwrap();
+    assert_eq!(output, "ABC\n3 c\n5\n");
+}
+
+#[test]
+fn test_indirect_call_unknown_function() {
+    let err = run_awk(r#"BEGIN { f = "nope"; @f(1) }"#, "").unwrap_err();
+    assert!(
+        err.contains("function `nope' called indirectly through `f' is not defined"),
+        "{}",
+        err
+    );
+}
+
+// === Bit Manipulation Functions ===
+
+#[test]
+fn test_bitwise_functions() {
+    let output = run_awk(
+        r#"BEGIN {
+        print and(12, 10), or(12, 10), xor(12, 10), and(15, 7, 3)
+        print compl(0), lshift(1, 10), rshift(1024, 3), and(7.9, 3)
+    }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "8 14 6 3\n9007199254740991 1024 128 3\n");
+}
+
+#[test]
+fn test_bitwise_flags_field() {
+    // Test individual flag bits of a field
+    let output = run_awk(
+        r#"{ printf "%s:%s%s%s\n", $1, and($2, 1) ? "r" : "-", and($2, 2) ? "w" : "-", and($2, 4) ? "x" : "-" }"#,
+        "a 5\nb 3\n",
+    )
+    .unwrap();
+    assert_eq!(output, "a:r-x\nb:rw-\n");
+}
+
+#[test]
+fn test_bitwise_negative_operand() {
+    for (program, message) in [
+        (
+            "BEGIN { and(1, -2) }",
+            "and: argument 2 negative value -2 is not allowed",
+        ),
+        (
+            "BEGIN { compl(-1) }",
+            "compl: argument 1 negative value -1 is not allowed",
+        ),
+        (
+            "BEGIN { lshift(1, -1) }",
+            "lshift: argument 2 negative value -1 is not allowed",
+        ),
+        (
+            "BEGIN { xor(1) }",
+            "xor: called with less than two arguments",
+        ),
+    ] {
+        let err = run_awk(program, "").unwrap_err();
+        assert!(err.contains(message), "{}: {}", program, err);
+    }
+}
+
+#[test]
+fn test_intdiv() {
+    let output = run_awk(
+        r#"BEGIN {
+        intdiv(17, 5, r); print r["quotient"], r["remainder"]
+        intdiv(-7.9, 2, r); print r["quotient"], r["remainder"]
+    }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "3 2\n-3 -1\n");
+
+    let err = run_awk("BEGIN { intdiv(1, 0, r) }", "").unwrap_err();
+    assert!(
+        err.contains("intdiv: division by zero attempted"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_strtonum() {
+    let output = run_awk(
+        r#"BEGIN { print strtonum("0x1F"), strtonum("0XfF"), strtonum("017"), strtonum("018"), strtonum("12.5abc") }
+        { print strtonum($1), strtonum($2) }"#,
+        "0x1A 011\n",
+    )
+    .unwrap();
+    assert_eq!(output, "31 255 15 18 12.5\n26 9\n");
+}
+
+#[test]
+fn test_strtonum_input_fields() {
+    let output = run_awk(
+        r#"{ split($2, a, ","); print strtonum($1), strtonum(a[1]), strtonum(a[2]), $1 + 0 }"#,
+        "017 0x10,42\n",
+    )
+    .unwrap();
+    assert_eq!(output, "15 16 42 17\n");
+}
+
+#[test]
+fn test_hex_and_octal_constants() {
+    let output = run_awk(
+        r#"BEGIN { print 0x1F, 0xff + 1, 011, 018, 011.5, and(0xF0, 0x3C) }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "31 256 9 18 11.5 48\n");
+}
+
 // === GAWK Extensions ===
 
 #[test]
@@ -1390,6 +2426,62 @@ fn test_asorti() {
     assert_eq!(output, "apple\nbanana\ncherry\n");
 }
 
+#[test]
+fn test_asort_types() {
+    // Numbers sort numerically before strings; subarrays come last, copied whole
+    let output = run_awk(
+        r#"BEGIN {
+        a[1] = 10; a[2] = "apple"; a[3]["x"] = 1; a[4] = 9; a[5] = "10"
+        n = asort(a, b)
+        for (i = 1; i <= n; i++)
+            print i, isarray(b[i]) ? "array " b[i]["x"] : b[i] " " typeof(b[i])
+    }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        "1 9 number\n2 10 number\n3 10 string\n4 apple string\n5 array 1\n"
+    );
+}
+
+#[test]
+fn test_asort_how() {
+    let output = run_awk(
+        r#"function by_length(i1, v1, i2, v2) { return length(v1) - length(v2) }
+    BEGIN {
+        a["x"] = 10; a["y"] = 9; a["z"] = 100
+        n = asort(a, b, "@val_num_desc")
+        print b[1], b[2], b[3]
+        n = asorti(a, b, "@ind_str_desc")
+        print b[1], b[2], b[3]
+        a["x"] = "ccc"; a["y"] = "a"; a["z"] = "bb"
+        asort(a, a, "by_length")
+        print a[1], a[2], a[3]
+    }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "100 10 9\nz y x\na bb ccc\n");
+}
+
+#[test]
+fn test_asorti_numeric_indices() {
+    let output = run_awk(
+        r#"BEGIN {
+        a[10]; a[9]; a[100]
+        a[10] = a[9] = a[100] = 1
+        asorti(a, b)
+        print b[1], b[2], b[3]
+        asorti(a, b, "@ind_num_asc")
+        print b[1], b[2], b[3]
+    }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "10 100 9\n9 10 100\n");
+}
+
 #[test]
 fn test_patsplit() {
     // patsplit extracts matching fields
@@ -1636,8 +2728,8 @@ fn test_uninitialized_string() {
 #[test]
 fn test_numeric_string_gt_comparison() {
     let output = run_awk(r#"BEGIN { print ("10" > "9") }"#, "").unwrap();
-    // Numeric comparison: 10 > 9
-    assert_eq!(output, "1\n");
+    // String constants compare as strings: "10" < "9"
+    assert_eq!(output, "0\n");
 }
 
 #[test]
@@ -1732,6 +2824,32 @@ fn test_exit_with_code() {
     assert!(!output.contains("second begin"));
 }
 
+#[test]
+fn test_exit_skips_rest_of_action() {
+    let output = run_awk(
+        r#"BEGIN { print "a"; exit 3; print "b" } END { print "end" }"#,
+        "",
+    )
+    .unwrap();
+    assert_eq!(output, "a\nend\n");
+}
+
+#[test]
+fn test_exit_in_main_runs_end() {
+    let output = run_awk(
+        r#"NR == 2 { exit } { print } END { print "end" }"#,
+        "x\ny\nz",
+    )
+    .unwrap();
+    assert_eq!(output, "x\nend\n");
+}
+
+#[test]
+fn test_next_skips_rest_of_action() {
+    let output = run_awk(r#"{ next; print "unreachable" } END { print NR }"#, "a\nb").unwrap();
+    assert_eq!(output, "2\n");
+}
+
 #[test]
 fn test_print_redirect_truncate() {
     // print to file (truncate mode)